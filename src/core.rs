@@ -1,7 +1,12 @@
 pub mod ask_tag;
 pub mod card;
+pub mod cost;
 pub mod effect;
+pub mod history;
+pub mod locale;
 pub mod number;
 pub mod selector;
+pub mod token;
 pub mod turn_phase;
+pub mod turn_rule;
 pub mod zone;
@@ -1,3 +1,6 @@
+use crate::core::locale::{Locale, LocaleCatalog, LocaleKey};
+
+// localized_prompt はカタログにタグが無い場合のフォールバック
 #[derive(Clone)]
 #[allow(dead_code)]
 pub struct AskOptionTag {
@@ -6,14 +9,25 @@ pub struct AskOptionTag {
     default: Option<bool>,
 }
 
+#[allow(dead_code)]
 impl AskOptionTag {
-    pub fn new(tag: &str, localized_prompt: &str, default: Option<bool>) -> AskOptionTag {
-        AskOptionTag {
+    pub fn new(tag: &str, localized_prompt: &str, default: Option<bool>) -> Self {
+        Self {
             tag: tag.to_owned(),
             localized_prompt: localized_prompt.to_owned(),
             default,
         }
     }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn prompt<'c>(&'c self, catalog: &'c LocaleCatalog, locale: Locale) -> &'c str {
+        catalog
+            .get(locale, &LocaleKey::Prompt(self.tag.clone()))
+            .unwrap_or(&self.localized_prompt)
+    }
 }
 
 #[derive(Clone)]
@@ -23,11 +37,111 @@ pub struct AskCardTag {
     localized_prompt: String,
 }
 
+#[allow(dead_code)]
 impl AskCardTag {
-    pub fn new(tag: &str, localized_prompt: &str) -> AskCardTag {
-        AskCardTag {
+    pub fn new(tag: &str, localized_prompt: &str) -> Self {
+        Self {
+            tag: tag.to_owned(),
+            localized_prompt: localized_prompt.to_owned(),
+        }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn prompt<'c>(&'c self, catalog: &'c LocaleCatalog, locale: Locale) -> &'c str {
+        catalog
+            .get(locale, &LocaleKey::Prompt(self.tag.clone()))
+            .unwrap_or(&self.localized_prompt)
+    }
+}
+
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct AskPlayerTag {
+    tag: String,
+    localized_prompt: String,
+}
+
+#[allow(dead_code)]
+impl AskPlayerTag {
+    pub fn new(tag: &str, localized_prompt: &str) -> Self {
+        Self {
+            tag: tag.to_owned(),
+            localized_prompt: localized_prompt.to_owned(),
+        }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn prompt<'c>(&'c self, catalog: &'c LocaleCatalog, locale: Locale) -> &'c str {
+        catalog
+            .get(locale, &LocaleKey::Prompt(self.tag.clone()))
+            .unwrap_or(&self.localized_prompt)
+    }
+}
+
+// 「以下から1つを選ぶ」の質問。hide_impossible なら、今は実行できない選択肢を見せない
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct AskChoiceTag {
+    tag: String,
+    localized_prompt: String,
+    hide_impossible: bool,
+}
+
+#[allow(dead_code)]
+impl AskChoiceTag {
+    pub fn new(tag: &str, localized_prompt: &str, hide_impossible: bool) -> Self {
+        Self {
             tag: tag.to_owned(),
             localized_prompt: localized_prompt.to_owned(),
+            hide_impossible,
         }
     }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub const fn hide_impossible(&self) -> bool {
+        self.hide_impossible
+    }
+
+    pub fn prompt<'c>(&'c self, catalog: &'c LocaleCatalog, locale: Locale) -> &'c str {
+        catalog
+            .get(locale, &LocaleKey::Prompt(self.tag.clone()))
+            .unwrap_or(&self.localized_prompt)
+    }
+}
+
+// 選択肢の表示名。タグでカタログを引く
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct ChoiceLabel {
+    tag: String,
+    localized_label: String,
+}
+
+#[allow(dead_code)]
+impl ChoiceLabel {
+    pub fn new(tag: &str, localized_label: &str) -> Self {
+        Self {
+            tag: tag.to_owned(),
+            localized_label: localized_label.to_owned(),
+        }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn label<'c>(&'c self, catalog: &'c LocaleCatalog, locale: Locale) -> &'c str {
+        catalog
+            .get(locale, &LocaleKey::Prompt(self.tag.clone()))
+            .unwrap_or(&self.localized_label)
+    }
 }
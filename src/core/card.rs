@@ -1,4 +1,5 @@
 use crate::core::{
+    cost::CardCost,
     effect::{CardEffect, EffectTrigger},
     number::Number,
 };
@@ -7,13 +8,13 @@ use crate::core::{
 pub struct Card {
     pub name: String,
     pub localized_name: String,
-    pub cost: Number,
+    pub cost: CardCost,
     pub vp: Number,
     pub rules: Vec<(EffectTrigger, CardEffect)>,
     pub types: Vec<CardType>,
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 #[allow(dead_code)]
 pub enum CardType {
     Action,
@@ -22,4 +23,30 @@ pub enum CardType {
     Reaction,
     Curse,
     Attack,
+    Event,     // 購入できるランドスケープ。王国には入らない
+    Landscape, // イベント以外のランドスケープ（プロジェクト、ランドマーク、習性など）
+}
+
+#[allow(dead_code)]
+impl Card {
+    pub fn is_landscape(&self) -> bool {
+        self.types
+            .iter()
+            .any(|t| matches!(t, CardType::Event | CardType::Landscape))
+    }
+
+    /// いずれかのルールの効果が条件を満たすか
+    pub fn any_rule(&self, pred: &dyn Fn(&CardEffect) -> bool) -> bool {
+        self.rules.iter().any(|(_, effect)| pred(effect))
+    }
+
+    /// 能力の中の、他のカード名への参照を付け替える
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        self.cost.rename_card(from, to);
+        self.vp.rename_card(from, to);
+        for (trigger, effect) in self.rules.iter_mut() {
+            trigger.rename_card(from, to);
+            effect.rename_card(from, to);
+        }
+    }
 }
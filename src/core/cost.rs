@@ -0,0 +1,83 @@
+use crate::core::number::Number;
+
+// コスト修正の有効範囲
+#[derive(Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum CostScope {
+    ThisTurn,     // このターンの間（橋）
+    WhileInPlay,  // 効果の元になったカードが場にある間（公道、王女）
+    Pile(String), // その名前のサプライの山のカードに、ゲーム終了まで（渡し船）
+}
+
+#[allow(dead_code)]
+impl CostScope {
+    /// 山の名前の参照を付け替える（拡張の名前空間付与用）
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        if let Self::Pile(name) = self {
+            if name == from {
+                *name = to.to_owned();
+            }
+        }
+    }
+}
+
+// カードに書かれたコスト。金は変わることがある（行商人など）ので数式で持つ
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct CardCost {
+    pub coin: Number,
+    pub potion: i32,
+    pub debt: i32,
+}
+
+#[allow(dead_code)]
+impl CardCost {
+    pub const fn coin(n: i32) -> Self {
+        Self {
+            coin: Number::Constant(n),
+            potion: 0,
+            debt: 0,
+        }
+    }
+
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        self.coin.rename_card(from, to);
+    }
+}
+
+// 計算したコスト
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[allow(dead_code)]
+pub struct Cost {
+    pub coin: i32,
+    pub potion: i32,
+    pub debt: i32,
+}
+
+#[allow(dead_code)]
+impl Cost {
+    pub const fn coin(n: i32) -> Self {
+        Self {
+            coin: n,
+            potion: 0,
+            debt: 0,
+        }
+    }
+
+    /// すべての要素が以下。$3ポーションと$4のように比べられないときは、どちら向きにも偽
+    pub const fn leq(&self, other: &Self) -> bool {
+        self.coin <= other.coin && self.potion <= other.potion && self.debt <= other.debt
+    }
+
+    /// より安い。すべての要素が以下で、どれかが少ない
+    pub fn less_than(&self, other: &Self) -> bool {
+        self.leq(other) && self != other
+    }
+
+    pub const fn plus_coin(&self, n: i32) -> Self {
+        Self {
+            coin: self.coin + n,
+            ..*self
+        }
+    }
+}
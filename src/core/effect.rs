@@ -1,7 +1,11 @@
 use crate::core::{
-    ask_tag::{AskCardTag, AskOptionTag},
+    ask_tag::{AskCardTag, AskChoiceTag, AskOptionTag, AskPlayerTag, ChoiceLabel},
+    cost::CostScope,
+    history::{HistoryKind, TurnScope},
     number::{Number, NumberRange},
-    selector::{CardNameSelector, CardSelector},
+    selector::{CardNameSelector, CardSelector, PileSelector, PlayerSelector},
+    turn_phase::TurnPhase,
+    turn_rule::TurnRule,
     zone::Zone,
 };
 
@@ -11,22 +15,71 @@ pub enum EffectCond {
     Leq(Number, Number),
     Geq(Number, Number),
     Eq(Number, Number),
-    CondAnd(Vec<EffectCond>),
-    CondOr(Vec<EffectCond>),
-    CondNot(Box<EffectCond>),
+    CondAnd(Vec<Self>),
+    CondOr(Vec<Self>),
+    CondNot(Box<Self>),
+    NamedIs(CardNameSelector), // 宣言されたカード名が条件を満たす。宣言されていなければ偽
+    MyTurn,                    // 効果を受けているプレイヤーの手番
+    PhaseIs(TurnPhase),
+    ThisInPlay,                                         // 効果の元になったカードが場にある
+    FocusedIs(CardNameSelector), // Focusしているカードが1枚以上あり、すべて条件を満たす。「アクションカードなら」はHasTypeで
+    PileEmpty(String),           // その名前のサプライの山が空
+    FirstTimeThisTurn(String),   // このカードがこのターン、そのタグでMarkThisTurnしていない
+    OpponentChose(String),       // この解決の中で、対戦相手がそのタグの選択肢を選んだ
+    Happened(TurnScope, HistoryKind, CardNameSelector), // CountHistoryが1以上
+    PileHasToken(PileSelector, String), // 該当する山のどれかに、その名前のトークンがある
+    PreviousTurnMine,            // 直前のターンが、効果を受けているプレイヤーのターン
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 #[allow(dead_code)]
 pub enum EffectTrigger {
     Attacked,       // Focus: 空, PreventDefaultで攻撃を無効化
     PlayAsAction,   // Focus: 空
     PlayAsTreasure, // Focus: 空
     CardPlayed,     // Focus: カード
-    Cleanup,        // Focus: 空, PreventDefaultで場にあっても捨て札にしない
+    Cleanup, // ThisCardで使う。Focus: このカード。クリーンアップで場から捨て札にする前の置換効果で、PreventDefaultで場に残る
     MyTurnStart,
     MyTurnEnd,
-    OncePerTurn(Box<EffectTrigger>),
+    OncePerTurn(Box<Self>),
+    CardBought,               // Focus: 買ったカード
+    CardGained,               // Focus: 獲得したカード
+    CardTrashed,              // Focus: 廃棄したカード
+    CardDiscarded, // Focus: 捨て札にしたカード。DiscardCard / DiscardSelect とクリーンアップのときだけで、MoveCard では起きない
+    DeckShuffled,  // Focus: 空。ThisCardなら混ぜられたそのカード
+    Continuous,    // 常時。ModifyCostなど、イベントを待たずに読まれる効果
+    WhileIn(Zone, Box<Self>), // このカードがそのゾーンにある間、自分のカードについてのイベントで有効。離れれば自動で無効になる
+    ThisCard(Box<Self>),      // このカード自身についてのイベントで有効（どこにあっても）
+    Replace(ActionKind), // Focus: 動こうとしているカード。その動作の前に解決され、PreventDefaultで取りやめ、Redirectで行き先を変える
+}
+
+// 置換効果の対象になる基本の動作
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(dead_code)]
+pub enum ActionKind {
+    Gain,
+    Draw,
+    Discard, // DiscardCard / DiscardSelect
+    Trash,
+    Move, // MoveCard / OrderCards
+}
+
+// 獲得元。サプライとサプライ外の山からは一番上のカードを、廃棄置き場からは好きなカードを獲得できる
+#[derive(Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum GainSource {
+    Supply,
+    NonSupply,
+    Trash,
+}
+
+// Chooseの選択肢。condがあれば、それを満たすときだけ実行可能とみなす
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct ChoiceOption {
+    pub label: ChoiceLabel,
+    pub cond: Option<EffectCond>,
+    pub effect: CardEffect,
 }
 
 // カードの働きを記述するためのメタ言語
@@ -34,37 +87,43 @@ pub enum EffectTrigger {
 #[allow(dead_code)]
 pub enum CardEffect {
     Noop,
-    Sequence(Vec<CardEffect>),
-    AtomicSequence(Vec<CardEffect>), // 「不可能な指示は無視」ができない場合（改築の破棄→獲得など）に使う。SkipContinueを伝播
-    Optional(AskOptionTag, Box<CardEffect>),
+    Sequence(Vec<Self>),
+    AtomicSequence(Vec<Self>), // 「不可能な指示は無視」ができない場合（改築の破棄→獲得など）に使う。SkipContinueを伝播
+    Optional(AskOptionTag, Box<Self>),
+    Choose(AskChoiceTag, NumberRange<Number>, Vec<ChoiceOption>), // 選択肢から異なるものをn個選び、並び順に実行する
 
     // Select亜種 該当カードすべてを選択、プレイヤーの選択を必要としない
-    FocusAll(CardSelector, Box<CardEffect>),
+    FocusAll(CardSelector, Box<Self>),
 
     // Select系：カードを選択し、Focusの選択先を変更した上で、効果を適用する
-    Select(
-        AskCardTag,
-        NumberRange<Number>,
-        CardSelector,
-        Box<CardEffect>,
-    ), // n枚選択
+    Select(AskCardTag, NumberRange<Number>, CardSelector, Box<Self>), // n枚選択
 
-    TrashSelect(NumberRange<Number>, CardSelector, Box<CardEffect>), // Select亜種 手札から廃棄
+    TrashSelect(NumberRange<Number>, CardSelector, Box<Self>), // Select亜種 手札から廃棄
 
-    DiscardSelect(NumberRange<Number>, CardSelector, Box<CardEffect>), // Select亜種 手札を捨てるs
+    DiscardSelect(NumberRange<Number>, CardSelector, Box<Self>), // Select亜種 手札を捨てるs
 
     // デッキトップ公開・Focus
-    RevealTop(Number, Box<CardEffect>),
+    RevealTop(Number, Box<Self>),
 
-    LookAtTop(Number, Box<CardEffect>), // デッキトップを見るだけ
+    LookAtTop(Number, Box<Self>), // デッキトップを見るだけ
 
-    DrawFocus(Number, Box<CardEffect>), // ドローしてFocus
+    DrawFocus(Number, Box<Self>), // ドローしてFocus
 
     DrawFrom(CardSelector), // ドロー扱いで手札に加える
 
-    If(EffectCond, Box<CardEffect>),
-    While(EffectCond, Box<CardEffect>),
-    Until(EffectCond, Box<CardEffect>),
+    BindCards(String, CardSelector), // 該当カードに名前を付ける。名前はこのカードの解決が終わるまで有効
+    BindNumber(String, Number),      // その時点の値に名前を付ける
+
+    ModifyCost(CostScope, CardNameSelector, Number), // 該当カードのコストを増減する。0未満にはならない
+    MarkThisTurn(String), // FirstTimeThisTurn用に、このカードにターン中の印を付ける
+    DelayThisTurn(EffectTrigger, Box<Self>), // このターンの間、効果を使ったプレイヤーのイベントに反応する効果を登録する。使うたびに1つ増える
+
+    ForEach(CardSelector, Box<Self>), // 該当カード1枚ずつをFocusして実行する。終われば元のFocusに戻る
+    Repeat(Number, Box<Self>),        // n回繰り返す。nは最初に1回だけ計算する
+
+    If(EffectCond, Box<Self>),
+    While(EffectCond, Box<Self>),
+    Until(EffectCond, Box<Self>),
 
     UseCard(CardSelector),
 
@@ -72,15 +131,211 @@ pub enum CardEffect {
     PlusAction(Number),
     PlusBuy(Number),
     PlusCoin(Number),
+    PlusPotion(Number),
 
     TrashCard(CardSelector),
     DiscardCard(CardSelector),
-    GainCard(CardNameSelector),
-    GainCardToHand(CardNameSelector), // 職人はこっち
+    GainCard(GainSource, CardNameSelector, Zone), // 獲得元から条件に合うカード1枚を選んで、指定したゾーンに獲得する
+    GainCardFrom(CardSelector, Zone), // サプライや廃棄置き場にある、選んだカードそのものを獲得する
 
     MoveCard(CardSelector, Zone),
+    OrderCards(AskCardTag, CardSelector, Zone), // 並べる順番を選ばせてから移動する。全部同じカードなら聞かない
+    NameCard(AskCardTag, CardNameSelector, Box<Self>), // カード名を宣言させ、CardNameSelector::Named で参照できるようにする
+
+    ChoosePlayer(AskPlayerTag, PlayerSelector, Box<Self>), // 効果を使ったプレイヤーが1人選び、そのプレイヤーをtargetにする
+    ForEachPlayer(PlayerSelector, Box<Self>), // 該当するプレイヤーを順にtargetにして実行する
+    GiveCard(CardSelector, PlayerSelector, Zone), // targetのカードを指定したプレイヤー（該当者が複数なら先頭）のゾーンに渡す
 
-    AllOpponents(Box<CardEffect>),
-    AttackAllOpponents(Box<CardEffect>),
+    ChoosePile(AskCardTag, CardNameSelector, Box<Self>), // サプライの山を1つ選ばせ、PileSelector::Chosenで参照できるようにする。条件は山の一番上のカードで見る
+    AddPileTokens(PileSelector, String, Number), // 山にトークンを置く。負なら取り除くが、0未満にはならない
+    AddTokens(String, Number), // targetのトークンを増やす。負なら減らすが、0未満にはならない
+    ExtraTurn(PlayerSelector, Vec<TurnRule>), // 該当するプレイヤー（複数なら先頭）が、このターンの後に追加ターンを得る
+
+    AllOpponents(Box<Self>),
+    AttackAllOpponents(Box<Self>),
     PreventDefault, // 「○○する代わりに」の、元の動作を無効化するやつ
+    Redirect(Zone), // 置換効果の中で、元の動作の行き先を変える（見張り塔、玉璽など）
+}
+
+#[allow(dead_code)]
+impl CardEffect {
+    /// 直下の子の効果
+    pub fn children(&self) -> Vec<&Self> {
+        use CardEffect::*;
+        match self {
+            Sequence(effects) | AtomicSequence(effects) => effects.iter().collect(),
+            Choose(_, _, options) => options.iter().map(|o| &o.effect).collect(),
+            Optional(_, effect)
+            | FocusAll(_, effect)
+            | Select(_, _, _, effect)
+            | TrashSelect(_, _, effect)
+            | DiscardSelect(_, _, effect)
+            | RevealTop(_, effect)
+            | LookAtTop(_, effect)
+            | DrawFocus(_, effect)
+            | NameCard(_, _, effect)
+            | ChoosePlayer(_, _, effect)
+            | ChoosePile(_, _, effect)
+            | ForEachPlayer(_, effect)
+            | ForEach(_, effect)
+            | Repeat(_, effect)
+            | If(_, effect)
+            | While(_, effect)
+            | Until(_, effect)
+            | AllOpponents(effect)
+            | AttackAllOpponents(effect) => vec![effect],
+            _ => vec![],
+        }
+    }
+
+    /// 効果の木のどこかに条件を満たす効果があるか
+    pub fn contains(&self, pred: &dyn Fn(&Self) -> bool) -> bool {
+        pred(self) || self.children().iter().any(|e| e.contains(pred))
+    }
+
+    // vanilla_effect は +0購入 も含むので、定数0は除く
+    pub fn gives_buy(&self) -> bool {
+        self.contains(&|e| match e {
+            Self::PlusBuy(Number::Constant(n)) => *n > 0,
+            Self::PlusBuy(_) => true,
+            _ => false,
+        })
+    }
+
+    // 相手に廃棄させる効果（山賊など）は廃棄手段に数えない
+    pub fn trashes_own_cards(&self) -> bool {
+        use CardEffect::*;
+        match self {
+            AllOpponents(_) | AttackAllOpponents(_) => false,
+            ForEachPlayer(PlayerSelector::Opponents, _) => false,
+            TrashCard(_) | TrashSelect(..) => true,
+            _ => self.children().iter().any(|e| e.trashes_own_cards()),
+        }
+    }
+
+    /// カード名や山の名前の参照を付け替える（拡張の名前空間付与用）
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        use CardEffect::*;
+        match self {
+            Sequence(effects) | AtomicSequence(effects) => {
+                for effect in effects.iter_mut() {
+                    effect.rename_card(from, to);
+                }
+            }
+            Choose(_, range, options) => {
+                range.rename_card(from, to);
+                for option in options.iter_mut() {
+                    if let Some(cond) = option.cond.as_mut() {
+                        cond.rename_card(from, to);
+                    }
+                    option.effect.rename_card(from, to);
+                }
+            }
+            Optional(_, effect)
+            | ChoosePlayer(_, _, effect)
+            | ForEachPlayer(_, effect)
+            | AllOpponents(effect)
+            | AttackAllOpponents(effect) => effect.rename_card(from, to),
+            FocusAll(selector, effect) | ForEach(selector, effect) => {
+                selector.rename_card(from, to);
+                effect.rename_card(from, to);
+            }
+            Select(_, range, selector, effect)
+            | TrashSelect(range, selector, effect)
+            | DiscardSelect(range, selector, effect) => {
+                range.rename_card(from, to);
+                selector.rename_card(from, to);
+                effect.rename_card(from, to);
+            }
+            RevealTop(n, effect)
+            | LookAtTop(n, effect)
+            | DrawFocus(n, effect)
+            | Repeat(n, effect) => {
+                n.rename_card(from, to);
+                effect.rename_card(from, to);
+            }
+            NameCard(_, selector, effect) | ChoosePile(_, selector, effect) => {
+                selector.rename_card(from, to);
+                effect.rename_card(from, to);
+            }
+            If(cond, effect) | While(cond, effect) | Until(cond, effect) => {
+                cond.rename_card(from, to);
+                effect.rename_card(from, to);
+            }
+            DrawFrom(selector)
+            | BindCards(_, selector)
+            | UseCard(selector)
+            | TrashCard(selector)
+            | DiscardCard(selector) => selector.rename_card(from, to),
+            BindNumber(_, n)
+            | PlusDraw(n)
+            | PlusAction(n)
+            | PlusBuy(n)
+            | PlusCoin(n)
+            | PlusPotion(n)
+            | AddTokens(_, n) => n.rename_card(from, to),
+            ModifyCost(scope, selector, n) => {
+                scope.rename_card(from, to);
+                selector.rename_card(from, to);
+                n.rename_card(from, to);
+            }
+            GainCard(_, selector, zone) => {
+                selector.rename_card(from, to);
+                zone.rename_card(from, to);
+            }
+            GainCardFrom(selector, zone)
+            | MoveCard(selector, zone)
+            | OrderCards(_, selector, zone)
+            | GiveCard(selector, _, zone) => {
+                selector.rename_card(from, to);
+                zone.rename_card(from, to);
+            }
+            AddPileTokens(pile, _, n) => {
+                pile.rename_card(from, to);
+                n.rename_card(from, to);
+            }
+            Redirect(zone) => zone.rename_card(from, to),
+            _ => {}
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl EffectCond {
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        use EffectCond::*;
+        match self {
+            Leq(a, b) | Geq(a, b) | Eq(a, b) => {
+                a.rename_card(from, to);
+                b.rename_card(from, to);
+            }
+            CondAnd(conds) | CondOr(conds) => {
+                for cond in conds.iter_mut() {
+                    cond.rename_card(from, to);
+                }
+            }
+            CondNot(cond) => cond.rename_card(from, to),
+            NamedIs(selector) | FocusedIs(selector) | Happened(_, _, selector) => {
+                selector.rename_card(from, to);
+            }
+            PileEmpty(name) if name == from => *name = to.to_owned(),
+            PileHasToken(pile, _) => pile.rename_card(from, to),
+            _ => {}
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl EffectTrigger {
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        use EffectTrigger::*;
+        match self {
+            WhileIn(zone, trigger) => {
+                zone.rename_card(from, to);
+                trigger.rename_card(from, to);
+            }
+            OncePerTurn(trigger) | ThisCard(trigger) => trigger.rename_card(from, to),
+            _ => {}
+        }
+    }
 }
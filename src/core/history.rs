@@ -0,0 +1,17 @@
+// 履歴を読むターン
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(dead_code)]
+pub enum TurnScope {
+    ThisTurn,
+    PreviousTurn, // 今のターンの1つ前のターン（誰のターンでも）。右隣のプレイヤーの直前のターンなど
+}
+
+// ターン中に記録する出来事
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(dead_code)]
+pub enum HistoryKind {
+    Played, // 玉座の間で2回使えば2回記録する
+    Gained,
+    Bought,
+    Trashed,
+}
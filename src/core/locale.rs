@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use crate::core::{card::CardType, zone::Zone};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[allow(dead_code)]
+pub enum Locale {
+    Ja,
+    En,
+}
+
+// カタログのキー。カード名・意思決定タグはそれぞれの識別子（英語名・タグ文字列）で引く
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum LocaleKey {
+    CardName(String),
+    CardType(CardType),
+    Zone(Zone),
+    Expansion(String), // 拡張のID
+    Prompt(String),    // AskOptionTag / AskCardTag のタグ
+    Log(String),       // ログ文のテンプレート、{player} {card} などを埋め込む
+}
+
+#[derive(Clone, Default)]
+#[allow(dead_code)]
+pub struct LocaleCatalog {
+    entries: HashMap<(Locale, LocaleKey), String>,
+}
+
+#[allow(dead_code)]
+impl LocaleCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, locale: Locale, key: LocaleKey, text: &str) {
+        self.entries.insert((locale, key), text.to_owned());
+    }
+
+    pub fn get(&self, locale: Locale, key: &LocaleKey) -> Option<&str> {
+        self.entries.get(&(locale, key.clone())).map(String::as_str)
+    }
+
+    /// 他のカタログを取り込む。同じキーは後から取り込んだ方が優先
+    pub fn extend(&mut self, other: Self) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn with_card_names(mut self, locale: Locale, names: &[(&str, &str)]) -> Self {
+        for (name, text) in names {
+            self.insert(locale, LocaleKey::CardName((*name).to_owned()), text);
+        }
+        self
+    }
+
+    pub fn with_prompts(mut self, locale: Locale, prompts: &[(&str, &str)]) -> Self {
+        for (tag, text) in prompts {
+            self.insert(locale, LocaleKey::Prompt((*tag).to_owned()), text);
+        }
+        self
+    }
+
+    pub fn with_logs(mut self, locale: Locale, logs: &[(&str, &str)]) -> Self {
+        for (key, text) in logs {
+            self.insert(locale, LocaleKey::Log((*key).to_owned()), text);
+        }
+        self
+    }
+
+    /// 指定ロケールでカタログに登録されていないキーを列挙する（カタログの網羅性チェック用）
+    pub fn missing(&self, locale: Locale, keys: &[LocaleKey]) -> Vec<LocaleKey> {
+        keys.iter()
+            .filter(|k| self.get(locale, k).is_none())
+            .cloned()
+            .collect()
+    }
+
+    /// カード種別・ゾーン名・ログ文など、拡張に依存しない部分のカタログ
+    pub fn core() -> Self {
+        use CardType::*;
+        let mut catalog = Self::new();
+        for (t, ja, en) in [
+            (Action, "アクション", "Action"),
+            (Treasure, "財宝", "Treasure"),
+            (Victory, "勝利点", "Victory"),
+            (Reaction, "リアクション", "Reaction"),
+            (Curse, "呪い", "Curse"),
+            (Attack, "アタック", "Attack"),
+            (Event, "イベント", "Event"),
+            (Landscape, "ランドスケープ", "Landscape"),
+        ] {
+            catalog.insert(Locale::Ja, LocaleKey::CardType(t.clone()), ja);
+            catalog.insert(Locale::En, LocaleKey::CardType(t), en);
+        }
+        for (z, ja, en) in [
+            (Zone::Deck, "山札", "Deck"),
+            (Zone::Hand, "手札", "Hand"),
+            (Zone::Discard, "捨て札", "Discard pile"),
+            (Zone::Play, "場", "Play area"),
+            (Zone::Pending, "処理中", "Pending"),
+            (Zone::Aside, "脇", "Set aside"),
+            (Zone::Revealed, "公開中", "Revealed"),
+            (Zone::DeckTop, "山札の上", "Top of deck"),
+            (Zone::Supply, "サプライ", "Supply"),
+            (Zone::Trash, "廃棄置き場", "Trash"),
+        ] {
+            catalog.insert(Locale::Ja, LocaleKey::Zone(z.clone()), ja);
+            catalog.insert(Locale::En, LocaleKey::Zone(z), en);
+        }
+        catalog
+            .with_logs(
+                Locale::Ja,
+                &[
+                    ("play", "{player}は{card}を使用した"),
+                    ("draw", "{player}はカードを{count}枚引いた"),
+                    ("gain", "{player}は{card}を獲得した"),
+                    ("buy", "{player}は{card}を購入した"),
+                    ("trash", "{player}は{card}を廃棄した"),
+                    ("discard", "{player}は{card}を捨て札にした"),
+                    ("reveal", "{player}は{card}を公開した"),
+                    ("shuffle", "{player}は山札をシャッフルした"),
+                    ("turn_start", "{player}のターン"),
+                ],
+            )
+            .with_logs(
+                Locale::En,
+                &[
+                    ("play", "{player} plays {card}"),
+                    ("draw", "{player} draws {count} card(s)"),
+                    ("gain", "{player} gains {card}"),
+                    ("buy", "{player} buys {card}"),
+                    ("trash", "{player} trashes {card}"),
+                    ("discard", "{player} discards {card}"),
+                    ("reveal", "{player} reveals {card}"),
+                    ("shuffle", "{player} shuffles their deck"),
+                    ("turn_start", "{player}'s turn"),
+                ],
+            )
+    }
+}
+
+/// テンプレート中の {name} を置換する
+#[allow(dead_code)]
+pub fn fill_template(template: &str, args: &[(&str, &str)]) -> String {
+    args.iter().fold(template.to_owned(), |s, (name, value)| {
+        s.replace(&format!("{{{name}}}"), value)
+    })
+}
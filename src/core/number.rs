@@ -1,4 +1,7 @@
-use crate::core::selector::CardSelector;
+use crate::core::{
+    history::{HistoryKind, TurnScope},
+    selector::{CardNameSelector, CardSelector, PileSelector, PlayerSelector},
+};
 
 #[derive(Clone)]
 #[allow(dead_code)]
@@ -7,14 +10,26 @@ pub enum Number {
     CountCard(CardSelector),
     CountCost(CardSelector),
     CountEmptyPiles,
-    Plus(Box<Number>, Box<Number>),
-    Minus(Box<Number>, Box<Number>),
-    Times(Box<Number>, Box<Number>), // 乗算
-    Div(Box<Number>, Box<Number>),   // 整数除算、切り捨て
-    Mod(Box<Number>, Box<Number>),   // 剰余
+    Plus(Box<Self>, Box<Self>),
+    Minus(Box<Self>, Box<Self>),
+    Times(Box<Self>, Box<Self>), // 乗算
+    Div(Box<Self>, Box<Self>),   // 整数除算、切り捨て
+    Mod(Box<Self>, Box<Self>),   // 剰余
+    Bound(String),               // Bindで名前を付けた数。カードに付けた名前ならその枚数
+    Min(Box<Self>, Box<Self>),
+    Max(Box<Self>, Box<Self>),
+    CountDistinctNames(CardSelector), // 該当カードの名前の種類数
+    CountPile(String),                // その名前のサプライの山の残り枚数
+    CountTrash(CardNameSelector),     // 廃棄置き場の該当カードの枚数
+    PlayerCount,
+    TreasuresInPlay,                     // 自分の場の財宝の枚数（銀行など）
+    OfPlayer(PlayerSelector, Box<Self>), // 指定したプレイヤーから見た値。複数なら合計
+    CountHistory(TurnScope, HistoryKind, CardNameSelector), // そのターンに、プレイヤーが該当カードについてその出来事を起こした回数
+    CountPileTokens(PileSelector, String), // 山に置かれたその名前のトークンの数。複数の山なら合計
+    CountTokens(String),                   // プレイヤーのその名前のトークンの数
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 #[allow(dead_code)]
 pub enum NumberRange<N> {
     Exact(N),
@@ -35,4 +50,59 @@ impl NumberRange<i32> {
             AnyNumber => true,
         }
     }
+
+    /// 候補がcount個しか無いときに選べる個数。足りない分は「できるだけ」選ぶ
+    pub fn limit_to(&self, count: i32) -> Self {
+        use NumberRange::*;
+        match self {
+            Exact(m) => Exact((*m).min(count)),
+            UpTo(m) => UpTo((*m).min(count)),
+            AtLeast(m) => Range((*m).min(count), count),
+            Range(a, b) => Range((*a).min(count), (*b).min(count)),
+            AnyNumber => UpTo(count),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Number {
+    /// カード名の参照を付け替える（拡張の名前空間付与用）
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        use Number::*;
+        match self {
+            CountCard(selector) | CountCost(selector) | CountDistinctNames(selector) => {
+                selector.rename_card(from, to);
+            }
+            Plus(a, b)
+            | Minus(a, b)
+            | Times(a, b)
+            | Div(a, b)
+            | Mod(a, b)
+            | Min(a, b)
+            | Max(a, b) => {
+                a.rename_card(from, to);
+                b.rename_card(from, to);
+            }
+            CountPile(name) if name == from => *name = to.to_owned(),
+            CountTrash(selector) | CountHistory(_, _, selector) => selector.rename_card(from, to),
+            OfPlayer(_, n) => n.rename_card(from, to),
+            CountPileTokens(pile, _) => pile.rename_card(from, to),
+            _ => {}
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl NumberRange<Number> {
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        use NumberRange::*;
+        match self {
+            Exact(n) | UpTo(n) | AtLeast(n) => n.rename_card(from, to),
+            Range(a, b) => {
+                a.rename_card(from, to);
+                b.rename_card(from, to);
+            }
+            AnyNumber => {}
+        }
+    }
 }
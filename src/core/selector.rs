@@ -8,11 +8,14 @@ use crate::core::{
 #[allow(dead_code)]
 pub enum CardNameSelector {
     Name(String),
-    NameAnd(Vec<CardNameSelector>),
-    NameOr(Vec<CardNameSelector>),
-    NameNot(Box<CardNameSelector>),
+    NameAnd(Vec<Self>),
+    NameOr(Vec<Self>),
+    NameNot(Box<Self>),
     HasType(CardType),
-    Cost(Box<NumberRange<Number>>),
+    Cost(Box<NumberRange<Number>>), // 金の範囲。上限があればポーションや負債のあるカードは含まない
+    CostUpTo(Box<CardSelector>, Box<Number>), // 該当カード（複数なら先頭）のコストよりn金多いまで。ポーションや負債も超えない
+    CostLessThan(Box<CardSelector>), // 該当カードより安い。比べられないコストなら当てはまらない
+    Named, // NameCardで宣言されたカード。宣言されていなければどれにも当てはまらない
     Any,
 }
 
@@ -22,3 +25,70 @@ pub struct CardSelector {
     pub name: CardNameSelector,
     pub zone: Vec<Zone>,
 }
+
+// サプライの山の指定
+#[derive(Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum PileSelector {
+    Named(String), // その名前の山
+    OfFocused,     // Focusしているカード（先頭）が作られた山。獲得した後でも元の山を指す
+    Chosen,        // ChoosePileで選んだ山
+    All,           // サプライのすべての山
+}
+
+// プレイヤーの指定。順番は手番順で、Opponents は自分の左隣から
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum PlayerSelector {
+    Me,     // 効果を使ったプレイヤー（フレームのplayer）
+    Target, // 今効果を受けているプレイヤー（フレームのtarget）
+    Opponents,
+    AllPlayers,
+    LeftOfTarget, // targetの左隣（手番順で次）
+    RightOfTarget,
+}
+
+#[allow(dead_code)]
+impl CardNameSelector {
+    /// カード名の参照を付け替える（拡張の名前空間付与用）
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        use CardNameSelector::*;
+        match self {
+            Name(name) if name == from => *name = to.to_owned(),
+            NameAnd(selectors) | NameOr(selectors) => {
+                for s in selectors.iter_mut() {
+                    s.rename_card(from, to);
+                }
+            }
+            NameNot(s) => s.rename_card(from, to),
+            Cost(range) => range.rename_card(from, to),
+            CostUpTo(selector, n) => {
+                selector.rename_card(from, to);
+                n.rename_card(from, to);
+            }
+            CostLessThan(selector) => selector.rename_card(from, to),
+            _ => {}
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl CardSelector {
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        self.name.rename_card(from, to);
+        for zone in self.zone.iter_mut() {
+            zone.rename_card(from, to);
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl PileSelector {
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        if let Self::Named(name) = self {
+            if name == from {
+                *name = to.to_owned();
+            }
+        }
+    }
+}
@@ -0,0 +1,7 @@
+// プレイヤーに置くトークンの名前。拡張が独自の名前を使ってもよい
+pub const VP: &str = "VP"; // 勝利点トークン。calculate_vpに足す
+pub const COFFERS: &str = "Coffers"; // 財源。1つ使うと+1金
+pub const VILLAGERS: &str = "Villagers"; // 村人。1つ使うと+1アクション
+#[allow(dead_code)]
+pub const FAVORS: &str = "Favors";
+pub const DEBT: &str = "Debt";
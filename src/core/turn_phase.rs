@@ -1,4 +1,4 @@
-#[derive(Clone)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[allow(dead_code)]
 pub enum TurnPhase {
     Action,
@@ -0,0 +1,9 @@
+// 追加ターンに付く決まり
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[allow(dead_code)]
+pub enum TurnRule {
+    HandSize(i32),     // 前哨地：この追加ターンのために引く手札の枚数
+    NoBuy,             // 使節団：カードを購入できない
+    PlayFromHand(i32), // 航海：手札から使えるカードの枚数
+    Possessed, // 憑依：効果を使ったプレイヤーがすべての決定を行う。獲得は操作する側へ、廃棄はターンの終わりまで脇に置く
+}
@@ -1,4 +1,6 @@
-#[derive(Clone)]
+use crate::core::selector::PlayerSelector;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 #[allow(dead_code)]
 pub enum Zone {
     // 実在のゾーン。配置対象としてもよい
@@ -9,10 +11,31 @@ pub enum Zone {
     Pending,
     Aside,
     Revealed,
+    Mat(String), // プレイヤーのマット（島、原住民の村、酒場など）
 
     // 以下は仮想的なゾーン
     DeckTop, // デッキの一番上。配置対象としてもよい
     AllMyCards,
     Focused,
     Itself,
+    Bound(String), // Bindで名前を付けたカード。移動した後でも同じカードを指す
+
+    // プレイヤーの物ではないゾーン。配置対象にはできない
+    Supply,             // サプライの各山の一番上
+    SupplyPile(String), // その名前のサプライの山の一番上
+    Trash,
+
+    OfPlayer(PlayerSelector, Box<Self>), // 他のプレイヤーのゾーン
+}
+
+#[allow(dead_code)]
+impl Zone {
+    /// 山の名前の参照を付け替える（拡張の名前空間付与用）
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        match self {
+            Self::SupplyPile(name) if name == from => *name = to.to_owned(),
+            Self::OfPlayer(_, zone) => zone.rename_card(from, to),
+            _ => {}
+        }
+    }
 }
@@ -1,3 +1,4 @@
 pub mod base;
 pub mod basic_supply;
 pub mod card_util;
+pub mod registry;
@@ -2,9 +2,12 @@ use std::collections::HashMap;
 use std::vec;
 
 use crate::core::{
-    ask_tag::{AskCardTag, AskOptionTag},
+    ask_tag::{AskCardTag, AskChoiceTag, AskOptionTag, ChoiceLabel},
     card::{Card, CardType::*},
-    effect::{CardEffect::*, EffectCond::*, EffectTrigger::*},
+    cost::CardCost,
+    effect::{CardEffect::*, ChoiceOption, EffectCond::*, EffectTrigger::*, GainSource},
+    history::{HistoryKind, TurnScope},
+    locale::{Locale, LocaleCatalog, LocaleKey},
     number::{Number::*, NumberRange::*},
     selector::{
         CardNameSelector::{self, *},
@@ -13,6 +16,7 @@ use crate::core::{
     zone::Zone,
 };
 use crate::expansions::card_util::*;
+use crate::expansions::registry::Expansion;
 
 /* ドミニオン 基本セット（第2版）
 カードリスト
@@ -82,20 +86,23 @@ pub fn moat() -> Card {
     Card {
         name: "Moat".to_owned(),
         localized_name: "堀".to_owned(),
-        cost: Constant(2),
+        cost: CardCost::coin(2),
         vp: Constant(0),
         rules: vec![
             (PlayAsAction, Sequence(vec![PlusDraw(Constant(2))])),
-            (Attacked, Sequence(vec![PreventDefault])),
+            (
+                WhileIn(Zone::Hand, Box::new(Attacked)),
+                Sequence(vec![PreventDefault]),
+            ),
         ],
         types: vec![Action, Reaction],
     }
 }
 
 // 家臣 +2金、デッキの上から1枚を公開して、アクションカードだった場合、そのカードを使用してもよい。
-pub fn chancellor() -> Card {
+pub fn vassal() -> Card {
     simple_action_card(
-        "Chancellor",
+        "Vassal",
         "家臣",
         3,
         false,
@@ -112,7 +119,7 @@ pub fn chancellor() -> Card {
                     },
                     // 使ってもよい
                     Box::new(Optional(
-                        AskOptionTag::new("chancellor", "このカードを使用しますか？", Some(true)),
+                        AskOptionTag::new("vassal", "このカードを使用しますか？", Some(true)),
                         Box::new(Sequence(vec![UseCard(focused()), DiscardCard(focused())])),
                     )),
                 )),
@@ -128,7 +135,11 @@ pub fn workshop() -> Card {
         "工房",
         3,
         false,
-        GainCard(CardNameSelector::Cost(Box::new(UpTo(Constant(4))))),
+        GainCard(
+            GainSource::Supply,
+            CardNameSelector::Cost(Box::new(UpTo(Constant(4)))),
+            Zone::Discard,
+        ),
     )
 }
 
@@ -138,29 +149,31 @@ pub fn merchant() -> Card {
         "Merchant",
         "商人",
         3,
-        vec![
-            (
-                PlayAsAction,
-                Sequence(vec![PlusDraw(Constant(1)), PlusAction(Constant(1))]),
-            ),
-            (
-                // 直訳：カードがプレイされたとき、今プレイされた銀貨の枚数が場の銀貨の枚数と等しい場合（意訳：このターンはじめて銀貨がプレイされたなら）、+1金
-                CardPlayed,
-                Sequence(vec![If(
-                    Eq(
-                        CountCard(CardSelector {
-                            name: CardNameSelector::Name("Silver".to_owned()),
-                            zone: vec![Zone::Focused],
-                        }),
-                        CountCard(CardSelector {
-                            name: CardNameSelector::Name("Silver".to_owned()),
-                            zone: vec![Zone::Play],
-                        }),
-                    ),
-                    Box::new(PlusCoin(Constant(1))),
-                )]),
-            ),
-        ],
+        vec![(
+            PlayAsAction,
+            Sequence(vec![
+                PlusDraw(Constant(1)),
+                PlusAction(Constant(1)),
+                // 使うたびに登録するので、玉座の間で2回使えば+1金も2回
+                DelayThisTurn(
+                    CardPlayed,
+                    Box::new(If(
+                        CondAnd(vec![
+                            FocusedIs(CardNameSelector::Name("Silver".to_owned())),
+                            Eq(
+                                CountHistory(
+                                    TurnScope::ThisTurn,
+                                    HistoryKind::Played,
+                                    CardNameSelector::Name("Silver".to_owned()),
+                                ),
+                                Constant(1),
+                            ),
+                        ]),
+                        Box::new(PlusCoin(Constant(1))),
+                    )),
+                ),
+            ]),
+        )],
         vec![Action],
     )
 }
@@ -200,10 +213,14 @@ pub fn remodel() -> Card {
         TrashSelect(
             Exact(Constant(1)),
             hand(),
-            Box::new(GainCard(CardNameSelector::Cost(Box::new(UpTo(Plus(
-                Box::new(CountCost(focused())),
-                Box::new(Constant(2)),
-            )))))),
+            Box::new(Sequence(vec![
+                BindCards("trashed".to_owned(), focused()),
+                GainCard(
+                    GainSource::Supply,
+                    CardNameSelector::CostUpTo(Box::new(bound("trashed")), Box::new(Constant(2))),
+                    Zone::Discard,
+                ),
+            ])),
         ),
     )
 }
@@ -251,7 +268,7 @@ pub fn throne_room() -> Card {
                 name: CardNameSelector::HasType(Action),
                 zone: vec![Zone::Hand],
             },
-            Box::new(Sequence(vec![UseCard(focused()), UseCard(focused())])),
+            Box::new(Repeat(Constant(2), Box::new(UseCard(focused())))),
         ),
     )
 }
@@ -297,7 +314,7 @@ pub fn militia() -> Card {
     )
 }
 
-// 役人 +2金、対戦相手は手札に勝利点カードがあれば1枚選んでデッキトップに置く。
+// 役人 銀貨1枚を山札の上に獲得する、対戦相手は手札に勝利点カードがあれば1枚選んでデッキトップに置く。
 pub fn bureaucrat() -> Card {
     simple_action_card(
         "Bureaucrat",
@@ -305,7 +322,11 @@ pub fn bureaucrat() -> Card {
         4,
         true,
         Sequence(vec![
-            PlusCoin(Constant(2)),
+            GainCard(
+                GainSource::Supply,
+                CardNameSelector::Name("Silver".to_owned()),
+                Zone::DeckTop,
+            ),
             AttackAllOpponents(Box::new(Select(
                 AskCardTag::new(
                     "bureaucrat",
@@ -327,7 +348,7 @@ pub fn gardens() -> Card {
     Card {
         name: "Gardens".to_owned(),
         localized_name: "庭園".to_owned(),
-        cost: Constant(4),
+        cost: CardCost::coin(4),
         vp: Div(Box::new(CountCard(all_my_cards())), Box::new(Constant(10))),
         rules: vec![],
         types: vec![Victory],
@@ -353,9 +374,35 @@ pub fn sentry() -> Card {
                 Constant(2),
                 Box::new(Sequence(vec![
                     MoveCard(focused(), Zone::Pending),
-                    TrashSelect(AnyNumber, in_zone(Zone::Pending), Box::new(Noop)),
-                    DiscardSelect(AnyNumber, in_zone(Zone::Pending), Box::new(Noop)),
-                    MoveCard(in_zone(Zone::Pending), Zone::DeckTop),
+                    ForEach(
+                        in_zone(Zone::Pending),
+                        Box::new(Choose(
+                            AskChoiceTag::new("sentry_each", "このカードをどうしますか？", false),
+                            Exact(Constant(1)),
+                            vec![
+                                ChoiceOption {
+                                    label: ChoiceLabel::new("sentry_trash", "廃棄する"),
+                                    cond: None,
+                                    effect: TrashCard(focused()),
+                                },
+                                ChoiceOption {
+                                    label: ChoiceLabel::new("sentry_discard", "捨て札にする"),
+                                    cond: None,
+                                    effect: DiscardCard(focused()),
+                                },
+                                ChoiceOption {
+                                    label: ChoiceLabel::new("sentry_keep", "山札に戻す"),
+                                    cond: None,
+                                    effect: Noop,
+                                },
+                            ],
+                        )),
+                    ),
+                    OrderCards(
+                        AskCardTag::new("sentry", "デッキトップに戻す順番を選んでください"),
+                        in_zone(Zone::Pending),
+                        Zone::DeckTop,
+                    ),
                 ])),
             ),
         ]),
@@ -399,14 +446,18 @@ pub fn mine() -> Card {
                     zone: vec![Zone::Hand],
                 },
                 Box::new(AtomicSequence(vec![
-                    TrashCard(focused()),
-                    GainCard(CardNameSelector::NameAnd(vec![
-                        CardNameSelector::HasType(Treasure),
-                        CardNameSelector::Cost(Box::new(UpTo(Plus(
-                            Box::new(CountCost(focused())),
-                            Box::new(Constant(3)),
-                        )))),
-                    ])),
+                    BindCards("trashed".to_owned(), focused()),
+                    GainCard(
+                        GainSource::Supply,
+                        CardNameSelector::NameAnd(vec![
+                            CardNameSelector::HasType(Treasure),
+                            CardNameSelector::CostUpTo(
+                                Box::new(bound("trashed")),
+                                Box::new(Constant(3)),
+                            ),
+                        ]),
+                        Zone::Hand,
+                    ),
                 ])),
             )),
         ),
@@ -486,7 +537,11 @@ pub fn bandit() -> Card {
         5,
         true,
         Sequence(vec![
-            GainCard(CardNameSelector::Name("Gold".to_owned())),
+            GainCard(
+                GainSource::Supply,
+                CardNameSelector::Name("Gold".to_owned()),
+                Zone::Discard,
+            ),
             AttackAllOpponents(Box::new(RevealTop(
                 Constant(2),
                 Box::new(TrashSelect(
@@ -511,9 +566,11 @@ pub fn witch() -> Card {
         true,
         Sequence(vec![
             PlusDraw(Constant(2)),
-            AttackAllOpponents(Box::new(GainCard(CardNameSelector::Name(
-                "Curse".to_owned(),
-            )))),
+            AttackAllOpponents(Box::new(GainCard(
+                GainSource::Supply,
+                CardNameSelector::Name("Curse".to_owned()),
+                Zone::Discard,
+            ))),
         ]),
     )
 }
@@ -526,7 +583,11 @@ pub fn artisan() -> Card {
         6,
         false,
         Sequence(vec![
-            GainCardToHand(CardNameSelector::Cost(Box::new(UpTo(Constant(5))))),
+            GainCard(
+                GainSource::Supply,
+                CardNameSelector::Cost(Box::new(UpTo(Constant(5)))),
+                Zone::Hand,
+            ),
             Select(
                 AskCardTag::new("artisan", "デッキトップに置くカードを選んでください"),
                 Exact(Constant(1)),
@@ -542,7 +603,7 @@ pub fn base_set() -> HashMap<String, Card> {
         cellar(),
         chapel(),
         moat(),
-        chancellor(),
+        vassal(),
         workshop(),
         merchant(),
         harbinger(),
@@ -570,3 +631,147 @@ pub fn base_set() -> HashMap<String, Card> {
     .map(|c| (c.name.clone(), c))
     .collect()
 }
+
+#[allow(dead_code)]
+pub fn base_set_catalog() -> LocaleCatalog {
+    LocaleCatalog::new()
+        .with_card_names(
+            Locale::Ja,
+            &[
+                ("Cellar", "地下貯蔵庫"),
+                ("Chapel", "礼拝堂"),
+                ("Moat", "堀"),
+                ("Vassal", "家臣"),
+                ("Workshop", "工房"),
+                ("Merchant", "商人"),
+                ("Harbinger", "前駆者"),
+                ("Village", "村"),
+                ("Remodel", "改築"),
+                ("Smithy", "鍛冶屋"),
+                ("Moneylender", "金貸し"),
+                ("Throne Room", "玉座の間"),
+                ("Poacher", "密猟者"),
+                ("Militia", "民兵"),
+                ("Bureaucrat", "役人"),
+                ("Gardens", "庭園"),
+                ("Market", "市場"),
+                ("Sentry", "衛兵"),
+                ("Council Room", "議事堂"),
+                ("Laboratory", "研究所"),
+                ("Mine", "鉱山"),
+                ("Festival", "祝祭"),
+                ("Library", "書庫"),
+                ("Bandit", "山賊"),
+                ("Witch", "魔女"),
+                ("Artisan", "職人"),
+            ],
+        )
+        .with_card_names(
+            Locale::En,
+            &[
+                ("Cellar", "Cellar"),
+                ("Chapel", "Chapel"),
+                ("Moat", "Moat"),
+                ("Vassal", "Vassal"),
+                ("Workshop", "Workshop"),
+                ("Merchant", "Merchant"),
+                ("Harbinger", "Harbinger"),
+                ("Village", "Village"),
+                ("Remodel", "Remodel"),
+                ("Smithy", "Smithy"),
+                ("Moneylender", "Moneylender"),
+                ("Throne Room", "Throne Room"),
+                ("Poacher", "Poacher"),
+                ("Militia", "Militia"),
+                ("Bureaucrat", "Bureaucrat"),
+                ("Gardens", "Gardens"),
+                ("Market", "Market"),
+                ("Sentry", "Sentry"),
+                ("Council Room", "Council Room"),
+                ("Laboratory", "Laboratory"),
+                ("Mine", "Mine"),
+                ("Festival", "Festival"),
+                ("Library", "Library"),
+                ("Bandit", "Bandit"),
+                ("Witch", "Witch"),
+                ("Artisan", "Artisan"),
+            ],
+        )
+        .with_prompts(
+            Locale::Ja,
+            &[
+                ("cellar", "捨て札にするカードを選んでください"),
+                ("vassal", "このカードを使用しますか？"),
+                ("harbinger", "デッキトップに置くカードを選んでください"),
+                ("moneylender", "銅貨を破棄しますか？"),
+                ("throne_room", "使用するカードを選んでください"),
+                (
+                    "bureaucrat",
+                    "デッキトップに置く勝利点カードを選んでください",
+                ),
+                ("mine", "財宝を破棄しますか？"),
+                ("library", "このカードを脇に避けますか？"),
+                ("sentry_each", "このカードをどうしますか？"),
+                ("sentry_trash", "廃棄する"),
+                ("sentry_discard", "捨て札にする"),
+                ("sentry_keep", "山札に戻す"),
+                ("sentry", "デッキトップに戻す順番を選んでください"),
+                ("artisan", "デッキトップに置くカードを選んでください"),
+            ],
+        )
+        .with_prompts(
+            Locale::En,
+            &[
+                ("cellar", "Choose cards to discard"),
+                ("vassal", "Play this card?"),
+                ("harbinger", "Choose a card to put onto your deck"),
+                ("moneylender", "Trash a Copper?"),
+                ("throne_room", "Choose an Action card to play twice"),
+                ("bureaucrat", "Choose a Victory card to put onto your deck"),
+                ("mine", "Trash a Treasure?"),
+                ("library", "Set this card aside?"),
+                ("sentry_each", "What do you do with this card?"),
+                ("sentry_trash", "Trash it"),
+                ("sentry_discard", "Discard it"),
+                ("sentry_keep", "Put it back"),
+                (
+                    "sentry",
+                    "Choose the order to put the cards back on your deck",
+                ),
+                ("artisan", "Choose a card to put onto your deck"),
+            ],
+        )
+}
+
+#[allow(dead_code)]
+pub fn base_expansion() -> Expansion {
+    let mut catalog = base_set_catalog();
+    catalog.insert(
+        Locale::Ja,
+        LocaleKey::Expansion("base".to_owned()),
+        "基本セット",
+    );
+    catalog.insert(
+        Locale::En,
+        LocaleKey::Expansion("base".to_owned()),
+        "Base Set",
+    );
+    Expansion {
+        id: "base".to_owned(),
+        display_name: "基本セット".to_owned(),
+        edition: 2,
+        cards: base_set().into_values().collect(),
+        catalog,
+        setup_hooks: vec![],
+        removed_cards: [
+            "Adventurer",
+            "Chancellor",
+            "Feast",
+            "Spy",
+            "Thief",
+            "Woodcutter",
+        ]
+        .map(str::to_owned)
+        .to_vec(),
+    }
+}
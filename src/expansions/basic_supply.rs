@@ -1,5 +1,7 @@
 use crate::core::card::Card;
+use crate::core::locale::{Locale, LocaleCatalog, LocaleKey};
 use crate::expansions::card_util::*;
+use crate::expansions::registry::{Expansion, SetupHook};
 use std::collections::HashMap;
 // 基本カード
 pub fn copper() -> Card {
@@ -38,3 +40,81 @@ pub fn basic_supply() -> HashMap<String, Card> {
     .map(|c| (c.name.clone(), c))
     .collect()
 }
+
+#[allow(dead_code)]
+pub fn basic_supply_catalog() -> LocaleCatalog {
+    LocaleCatalog::new()
+        .with_card_names(
+            Locale::Ja,
+            &[
+                ("Copper", "銅貨"),
+                ("Silver", "銀貨"),
+                ("Gold", "金貨"),
+                ("Estate", "屋敷"),
+                ("Duchy", "公領"),
+                ("Province", "属州"),
+                ("Curse", "呪い"),
+            ],
+        )
+        .with_card_names(
+            Locale::En,
+            &[
+                ("Copper", "Copper"),
+                ("Silver", "Silver"),
+                ("Gold", "Gold"),
+                ("Estate", "Estate"),
+                ("Duchy", "Duchy"),
+                ("Province", "Province"),
+                ("Curse", "Curse"),
+            ],
+        )
+}
+
+// 勝利点カードの山は2人なら8枚、それ以上なら12枚
+pub const fn victory_pile_size(players: usize) -> i32 {
+    if players <= 2 {
+        8
+    } else {
+        12
+    }
+}
+
+// 基本カードは拡張として登録する。山と初期デッキはここで用意する
+#[allow(dead_code)]
+pub fn basic_expansion() -> Expansion {
+    let mut catalog = basic_supply_catalog();
+    catalog.insert(
+        Locale::Ja,
+        LocaleKey::Expansion("basic".to_owned()),
+        "基本カード",
+    );
+    catalog.insert(
+        Locale::En,
+        LocaleKey::Expansion("basic".to_owned()),
+        "Basic cards",
+    );
+    Expansion {
+        id: "basic".to_owned(),
+        display_name: "基本カード".to_owned(),
+        edition: 2,
+        cards: basic_supply().into_values().collect(),
+        catalog,
+        setup_hooks: vec![
+            SetupHook::ExtraPile("Copper".to_owned(), |n| 60 - 7 * n as i32),
+            SetupHook::ExtraPile("Silver".to_owned(), |_| 40),
+            SetupHook::ExtraPile("Gold".to_owned(), |_| 30),
+            SetupHook::ExtraPile("Estate".to_owned(), victory_pile_size),
+            SetupHook::ExtraPile("Duchy".to_owned(), victory_pile_size),
+            SetupHook::ExtraPile("Province".to_owned(), |n| match n {
+                ..=2 => 8,
+                5 => 15,
+                6.. => 18,
+                _ => 12,
+            }),
+            SetupHook::ExtraPile("Curse".to_owned(), |n| 10 * (n.max(2) as i32 - 1)),
+            SetupHook::StartingCards("Copper".to_owned(), 7),
+            SetupHook::StartingCards("Estate".to_owned(), 3),
+        ],
+        removed_cards: vec![],
+    }
+}
@@ -3,6 +3,7 @@ use crate::core::{
         Card,
         CardType::{self, *},
     },
+    cost::CardCost,
     effect::{
         CardEffect,
         EffectTrigger::{self, *},
@@ -33,7 +34,7 @@ pub fn vanilla_action_card(
     Card {
         name: name.to_owned(),
         localized_name: localized_name.to_owned(),
-        cost: Constant(cost),
+        cost: CardCost::coin(cost),
         vp: Constant(0),
         rules: vec![(PlayAsAction, vanilla_effect(draw, action, buy, coin))],
         types: vec![Action],
@@ -44,7 +45,7 @@ pub fn vanilla_treasure_card(name: &str, localized_name: &str, cost: i32, coin:
     Card {
         name: name.to_owned(),
         localized_name: localized_name.to_owned(),
-        cost: Constant(cost),
+        cost: CardCost::coin(cost),
         vp: Constant(0),
         rules: vec![(PlayAsTreasure, vanilla_effect(0, 0, 0, coin))],
         types: vec![Treasure],
@@ -55,7 +56,7 @@ pub fn vanilla_vp_card(name: &str, localized_name: &str, cost: i32, vp: i32) ->
     Card {
         name: name.to_owned(),
         localized_name: localized_name.to_owned(),
-        cost: Constant(cost),
+        cost: CardCost::coin(cost),
         vp: Constant(vp),
         rules: vec![],
         types: vec![Victory],
@@ -65,7 +66,7 @@ pub fn vanilla_curse_card() -> Card {
     Card {
         name: "Curse".to_owned(),
         localized_name: "呪い".to_owned(),
-        cost: Constant(0),
+        cost: CardCost::coin(0),
         vp: Constant(-1),
         rules: vec![],
         types: vec![Curse],
@@ -81,7 +82,7 @@ pub fn simple_rule_card(
     Card {
         name: name.to_owned(),
         localized_name: localized_name.to_owned(),
-        cost: Constant(cost),
+        cost: CardCost::coin(cost),
         vp: Constant(0),
         rules,
         types,
@@ -142,3 +143,11 @@ pub fn in_zone(zone: Zone) -> CardSelector {
         zone: vec![zone],
     }
 }
+
+#[allow(dead_code)]
+pub fn bound(name: &str) -> CardSelector {
+    CardSelector {
+        name: Any,
+        zone: vec![Zone::Bound(name.to_owned())],
+    }
+}
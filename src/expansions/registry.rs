@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::ops::Index;
+
+use crate::{
+    core::{
+        card::Card,
+        card::CardType,
+        effect::{CardEffect, EffectTrigger},
+        locale::{Locale, LocaleCatalog, LocaleKey},
+        zone::Zone,
+    },
+    expansions::basic_supply::victory_pile_size,
+    game::{game::Game, player::PlayerData},
+};
+
+// ゲーム準備時に拡張が行う追加処理
+#[derive(Clone)]
+#[allow(dead_code)]
+pub enum SetupHook {
+    ExtraPile(String, fn(usize) -> i32), // 王国とは別にサプライに置く山（カード名、プレイヤー数から枚数を決める関数）
+    StartingCards(String, i32),          // 各プレイヤーの初期デッキに入れるカード（カード名、枚数）
+    SecretMat(String),                   // 持ち主しか中身を見られないマット（原住民の村）
+    PileTokenRule(String, EffectTrigger, Box<CardEffect>), // そのトークンが置かれた山のカードについてのイベントで解決する効果（禁輸など）
+}
+
+impl SetupHook {
+    /// カード名の参照を付け替える（拡張の名前空間付与用）
+    pub fn rename_card(&mut self, from: &str, to: &str) {
+        match self {
+            Self::ExtraPile(name, _) | Self::StartingCards(name, _) => {
+                if name == from {
+                    *name = to.to_owned();
+                }
+            }
+            Self::SecretMat(_) => {}
+            Self::PileTokenRule(_, trigger, effect) => {
+                trigger.rename_card(from, to);
+                effect.rename_card(from, to);
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub struct Expansion {
+    pub id: String,
+    pub display_name: String, // カタログに拡張名が無い場合のフォールバック
+    pub edition: u32,
+    pub cards: Vec<Card>,
+    pub catalog: LocaleCatalog,
+    pub setup_hooks: Vec<SetupHook>,
+    pub removed_cards: Vec<String>, // 前の版にしか無く、この版には入っていないカード
+}
+
+// カード名が衝突したときの扱い
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum ConflictPolicy {
+    Reject,
+    Namespace, // 後から登録した方を "拡張ID:カード名" にし、その拡張の中での名前の参照も付け替える
+}
+
+#[derive(Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum RegistryError {
+    DuplicateExpansion(String),
+    NameConflict {
+        name: String,
+        existing: String, // 既に登録されている拡張のID
+        incoming: String,
+    },
+    UnknownExpansion(String),
+    UnknownCard(String),
+    DuplicateCard(String), // 1つの拡張の中で同じ名前のカードが2枚ある
+}
+
+#[allow(dead_code)]
+struct RegisteredCard {
+    card: Card,
+    expansion: String,
+}
+
+#[derive(Default)]
+#[allow(dead_code)]
+pub struct ExpansionRegistry {
+    cards: HashMap<String, RegisteredCard>,
+    expansions: Vec<ExpansionInfo>,
+}
+
+#[allow(dead_code)]
+struct ExpansionInfo {
+    id: String,
+    display_name: String,
+    edition: u32,
+    card_names: Vec<String>, // 名前空間付与後の名前
+    catalog: LocaleCatalog,
+    setup_hooks: Vec<SetupHook>,
+    removed_cards: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl ExpansionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 拡張を登録する。Rejectで衝突した場合は何も登録しない
+    pub fn register(
+        &mut self,
+        expansion: Expansion,
+        policy: ConflictPolicy,
+    ) -> Result<(), RegistryError> {
+        if self.expansion(&expansion.id).is_some() {
+            return Err(RegistryError::DuplicateExpansion(expansion.id));
+        }
+
+        let mut cards = expansion.cards;
+        if let Some(i) =
+            (1..cards.len()).find(|i| cards[..*i].iter().any(|c| c.name == cards[*i].name))
+        {
+            return Err(RegistryError::DuplicateCard(cards[i].name.clone()));
+        }
+        let mut catalog = expansion.catalog;
+        let mut renames = vec![];
+        for card in cards.iter_mut() {
+            let Some(existing) = self.cards.get(&card.name) else {
+                continue;
+            };
+            match policy {
+                ConflictPolicy::Reject => {
+                    return Err(RegistryError::NameConflict {
+                        name: card.name.clone(),
+                        existing: existing.expansion.clone(),
+                        incoming: expansion.id,
+                    });
+                }
+                ConflictPolicy::Namespace => {
+                    let namespaced = format!("{}:{}", expansion.id, card.name);
+                    // カタログの表示名も新しい名前で引けるようにする
+                    for locale in [Locale::Ja, Locale::En] {
+                        let key = LocaleKey::CardName(card.name.clone());
+                        if let Some(text) = catalog.get(locale, &key).map(str::to_owned) {
+                            catalog.insert(locale, LocaleKey::CardName(namespaced.clone()), &text);
+                        }
+                    }
+                    renames.push((card.name.clone(), namespaced.clone()));
+                    card.name = namespaced;
+                }
+            }
+        }
+        // 拡張の中から名前で参照しているのは、その拡張自身のカード
+        let mut setup_hooks = expansion.setup_hooks;
+        let mut removed_cards = expansion.removed_cards;
+        for (from, to) in renames.iter() {
+            for card in cards.iter_mut() {
+                card.rename_card(from, to);
+            }
+            for hook in setup_hooks.iter_mut() {
+                hook.rename_card(from, to);
+            }
+            for name in removed_cards.iter_mut().filter(|n| *n == from) {
+                *name = to.clone();
+            }
+        }
+
+        let card_names = cards.iter().map(|c| c.name.clone()).collect();
+        for card in cards {
+            self.cards.insert(
+                card.name.clone(),
+                RegisteredCard {
+                    card,
+                    expansion: expansion.id.clone(),
+                },
+            );
+        }
+        self.expansions.push(ExpansionInfo {
+            id: expansion.id,
+            display_name: expansion.display_name,
+            edition: expansion.edition,
+            card_names,
+            catalog,
+            setup_hooks,
+            removed_cards,
+        });
+        Ok(())
+    }
+
+    fn expansion(&self, id: &str) -> Option<&ExpansionInfo> {
+        self.expansions.iter().find(|e| e.id == id)
+    }
+
+    pub fn expansion_ids(&self) -> Vec<&str> {
+        self.expansions.iter().map(|e| e.id.as_str()).collect()
+    }
+
+    pub fn display_name(&self, id: &str, locale: Locale) -> Option<&str> {
+        let info = self.expansion(id)?;
+        Some(
+            info.catalog
+                .get(locale, &LocaleKey::Expansion(id.to_owned()))
+                .unwrap_or(&info.display_name),
+        )
+    }
+
+    pub fn edition(&self, id: &str) -> Option<u32> {
+        self.expansion(id).map(|e| e.edition)
+    }
+
+    /// 拡張の前の版にしか無いカードか
+    pub fn is_removed_card(&self, id: &str, name: &str) -> bool {
+        self.expansion(id)
+            .is_some_and(|e| e.removed_cards.iter().any(|c| c == name))
+    }
+
+    pub fn card(&self, name: &str) -> Option<&Card> {
+        self.cards.get(name).map(|c| &c.card)
+    }
+
+    /// カードの出身拡張のID
+    pub fn origin(&self, name: &str) -> Option<&str> {
+        self.cards.get(name).map(|c| c.expansion.as_str())
+    }
+
+    /// カードの表示名を出身拡張のカタログから引く
+    pub fn card_display_name(&self, name: &str, locale: Locale) -> Option<&str> {
+        let info = self.expansion(self.origin(name)?)?;
+        info.catalog
+            .get(locale, &LocaleKey::CardName(name.to_owned()))
+    }
+
+    pub fn cards_of(&self, id: &str) -> Vec<&Card> {
+        self.expansion(id).map_or(vec![], |e| {
+            e.card_names
+                .iter()
+                .filter_map(|name| self.card(name))
+                .collect()
+        })
+    }
+
+    /// 王国カードの候補。準備処理で使うカード（基本カードなど）とランドスケープは王国に入らない
+    pub fn kingdom_cards(&self, ids: &[&str]) -> Vec<&Card> {
+        let setup_cards: Vec<&str> = self
+            .expansions
+            .iter()
+            .flat_map(|e| e.setup_hooks.iter())
+            .filter_map(|hook| match hook {
+                SetupHook::ExtraPile(name, _) | SetupHook::StartingCards(name, _) => {
+                    Some(name.as_str())
+                }
+                SetupHook::SecretMat(_) | SetupHook::PileTokenRule(..) => None,
+            })
+            .collect();
+        ids.iter()
+            .flat_map(|id| self.cards_of(id))
+            .filter(|card| !setup_cards.contains(&card.name.as_str()))
+            .filter(|card| !card.is_landscape())
+            .collect()
+    }
+
+    /// 選んだ拡張のカードでゲームを準備する。カタログの統合と拡張ごとの追加処理を行い、初期デッキをシャッフルする。
+    /// シードを指定すれば、初期デッキの並びはシードだけで決まる
+    pub fn setup_game(
+        &self,
+        expansions: &[&str],
+        players: Vec<PlayerData>,
+        seed: Option<u64>,
+    ) -> Result<Game<'_>, RegistryError> {
+        if let Some(i) = (1..expansions.len()).find(|i| expansions[..*i].contains(&expansions[*i]))
+        {
+            return Err(RegistryError::DuplicateExpansion(expansions[i].to_owned()));
+        }
+        let infos = expansions
+            .iter()
+            .map(|id| {
+                self.expansion(id)
+                    .ok_or_else(|| RegistryError::UnknownExpansion((*id).to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut catalog = LocaleCatalog::core();
+        for info in infos.iter() {
+            catalog.extend(info.catalog.clone());
+        }
+        let mut game = Game::new(players, catalog);
+        if let Some(seed) = seed {
+            game.set_seed(seed);
+        }
+        game.card_pool = self.cards.values().map(|c| &c.card).collect();
+
+        for hook in infos.iter().flat_map(|info| info.setup_hooks.iter()) {
+            match hook {
+                SetupHook::ExtraPile(name, n) => {
+                    let card = self
+                        .card(name)
+                        .ok_or_else(|| RegistryError::UnknownCard(name.clone()))?;
+                    let n = n(game.players.len());
+                    game.add_supply_pile(card, n);
+                }
+                SetupHook::StartingCards(name, n) => {
+                    let card = self
+                        .card(name)
+                        .ok_or_else(|| RegistryError::UnknownCard(name.clone()))?;
+                    let ids: Vec<_> = game.players.iter().map(|p| p.id).collect();
+                    for id in ids {
+                        for _ in 0..*n {
+                            game.put_card(id, Zone::Deck, card);
+                        }
+                    }
+                }
+                SetupHook::SecretMat(name) => game.secret_mats.push(name.clone()),
+                SetupHook::PileTokenRule(name, trigger, effect) => {
+                    game.pile_token_rules
+                        .push((name.clone(), trigger.clone(), *effect.clone()));
+                }
+            }
+        }
+        game.shuffle_starting_decks();
+        Ok(game)
+    }
+
+    /// 拡張を選んでゲームを準備し、王国カードの山を並べる。王国カードは選んだ拡張に含まれていること
+    pub fn setup_kingdom_game(
+        &self,
+        expansions: &[&str],
+        kingdom: &[&str],
+        players: Vec<PlayerData>,
+        seed: Option<u64>,
+    ) -> Result<Game<'_>, RegistryError> {
+        let cards = kingdom
+            .iter()
+            .map(|name| {
+                self.card(name)
+                    .filter(|_| self.origin(name).is_some_and(|id| expansions.contains(&id)))
+                    .ok_or_else(|| RegistryError::UnknownCard((*name).to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut game = self.setup_game(expansions, players, seed)?;
+        let n_players = game.players.len();
+        for card in cards {
+            let n = if card.types.contains(&CardType::Victory) {
+                victory_pile_size(n_players)
+            } else {
+                10
+            };
+            game.add_supply_pile(card, n);
+        }
+        Ok(game)
+    }
+}
+
+impl Index<&str> for ExpansionRegistry {
+    type Output = Card;
+
+    fn index(&self, name: &str) -> &Card {
+        self.card(name)
+            .unwrap_or_else(|| panic!("unknown card: {name}"))
+    }
+}
@@ -1,4 +1,9 @@
 pub mod card_instance;
+pub mod cost_modifier;
 pub mod effect_stack;
+pub mod extra_turn;
+#[allow(clippy::module_inception)]
 pub mod game;
 pub mod player;
+pub mod supply;
+pub mod turn_history;
@@ -7,13 +7,13 @@ pub struct CardInstance<'a> {
     pub card: &'a Card,
     pub id: CardInstanceId,
     pub address: CardAddress,
+    pub origin: Option<usize>, // 作られたサプライの山の番号
 }
 
 impl CardInstance<'_> {
     pub fn info(&self) -> CardInstanceInfo {
         CardInstanceInfo {
             name: self.card.name.clone(),
-            localized_name: self.card.localized_name.clone(),
             instance_id: self.id,
         }
     }
@@ -22,25 +22,25 @@ impl CardInstance<'_> {
 #[derive(Clone)]
 #[allow(dead_code)]
 pub enum CardAddress {
-    Supply(usize, usize),
+    Supply(usize), // 山の番号。サプライ外の山も含む
     PlayerOwned(PlayerId, Zone),
     Trash,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 #[allow(dead_code)]
 pub struct CardInstanceId {
     id: usize,
 }
 
 impl CardInstanceId {
-    pub fn new(id: usize) -> CardInstanceId {
-        CardInstanceId { id }
+    pub const fn new(id: usize) -> Self {
+        Self { id }
     }
 }
 
+// 表示名は持たない。表示時に Game::localized_card_name でプレイヤーごとに解決する
 pub struct CardInstanceInfo {
     pub name: String,
-    pub localized_name: String,
     pub instance_id: CardInstanceId,
 }
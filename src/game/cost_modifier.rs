@@ -0,0 +1,14 @@
+use crate::{
+    core::{cost::CostScope, selector::CardNameSelector},
+    game::card_instance::CardInstanceId,
+};
+
+// コスト修正1つ分。amountは負なら安くなる
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct CostModifier {
+    pub scope: CostScope,
+    pub source: Option<CardInstanceId>,
+    pub selector: CardNameSelector,
+    pub amount: i32,
+}
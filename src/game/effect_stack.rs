@@ -1,8 +1,9 @@
 use crate::{
     core::{
-        ask_tag::{AskCardTag, AskOptionTag},
-        effect::CardEffect,
+        ask_tag::{AskCardTag, AskChoiceTag, AskOptionTag, AskPlayerTag, ChoiceLabel},
+        effect::{ActionKind, CardEffect, ChoiceOption},
         number::NumberRange,
+        zone::Zone,
     },
     game::{
         card_instance::{CardInstanceId, CardInstanceInfo},
@@ -18,8 +19,42 @@ pub struct EffectStackFrame {
     pub target: PlayerId,
     pub effect_queue: VecDeque<CardEffect>,
     pub focus: Vec<CardInstanceId>,
+    pub named_card: Option<String>, // NameCardで宣言されたカード名。入れ子のフレームに引き継ぐ
     pub cause: Option<CardInstanceId>,
     pub atomic: bool,
+    pub resolution: usize, // push_effectごとの番号。Bindの名前はこの単位で共有する
+    pub replacing: Option<usize>, // 置換効果やリアクションの解決中なら、置き換えている保留中の動作や攻撃の番号
+    pub pile: Option<usize>,      // ChoosePileで選んだ山の番号
+    pub internal: Option<InternalStep>, // effect_queueが空になったら行うエンジン内部の処理
+}
+
+// エンジン内部の処理。カードの効果としては書けない
+#[derive(Clone)]
+#[allow(dead_code)]
+pub enum InternalStep {
+    ApplyReplacements(usize), // 保留中の動作に置換効果を1つずつ適用し、最後に動作を行う
+    ResolveAttack(usize, Box<CardEffect>), // リアクションで無効にされていなければ攻撃を行う
+}
+
+// 置換効果の解決を待っている動作。カード1枚ごとに作る
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct PendingAction {
+    pub kind: ActionKind,
+    pub player: PlayerId, // 置換効果の順番を選ぶプレイヤー
+    pub card: CardInstanceId,
+    pub zone: Zone, // 行き先。廃棄ならZone::Trash
+    pub cancelled: bool,
+    pub redirected: bool,
+    pub replacements: Vec<(CardInstanceId, CardEffect)>, // まだ適用していない置換効果と、その元のカード
+}
+
+// Bindで付けた名前の中身
+#[derive(Clone)]
+#[allow(dead_code)]
+pub enum Binding {
+    Cards(Vec<CardInstanceId>),
+    Number(i32),
 }
 
 #[allow(dead_code)]
@@ -34,7 +69,78 @@ pub enum EffectStepResult {
     ), // 次のStepはFocusした状態で
     AskTrash(PlayerId, NumberRange<i32>, Vec<CardInstanceInfo>), // 次のStepはFocusした状態で
     AskDiscard(PlayerId, NumberRange<i32>, Vec<CardInstanceInfo>), // 次のStepはFocusした状態で
-    AskOptional(PlayerId, AskOptionTag), // 答えがNoだったらそのスタックフレームをスキップ
-    SkipContinue,                        // 不可能な指示なので飛ばす
+    AskGain(PlayerId, Vec<CardInstanceInfo>), // 獲得するカードを1枚選ぶ。次のStepはFocusした状態で
+    AskOptional(PlayerId, AskOptionTag),      // 答えがNoだったらそのスタックフレームをスキップ
+    AskChoice(PlayerId, AskChoiceTag, NumberRange<i32>, Vec<ChoiceInfo>), // 選んだ選択肢の効果を実行する
+    AskOrder(PlayerId, AskCardTag, Vec<CardInstanceInfo>), // 候補全部を並べ替えて答える
+    AskCardName(PlayerId, AskCardTag, Vec<String>),        // カード名を1つ宣言する
+    AskPlayer(PlayerId, AskPlayerTag, Vec<PlayerId>),      // プレイヤーを1人選ぶ
+    AskPile(PlayerId, AskCardTag, Vec<(usize, String)>),   // サプライの山を1つ選ぶ。山の番号と名前
+    AskReplacement(PlayerId, Vec<CardInstanceInfo>), // 次に適用する置換効果の元のカードを1枚選ぶ
+    SkipContinue,                                    // 不可能な指示なので飛ばす
+    Impossible(ImpossibleReason), // 不可能な指示。SkipContinueと同じく飛ばし、理由を記録する
     End,
 }
+
+#[allow(dead_code)]
+impl EffectStepResult {
+    /// 質問に答えるプレイヤー
+    pub const fn asked_player_mut(&mut self) -> Option<&mut PlayerId> {
+        use EffectStepResult::*;
+        match self {
+            AskCard(player, ..)
+            | AskTrash(player, ..)
+            | AskDiscard(player, ..)
+            | AskGain(player, ..)
+            | AskOptional(player, ..)
+            | AskChoice(player, ..)
+            | AskOrder(player, ..)
+            | AskCardName(player, ..)
+            | AskPlayer(player, ..)
+            | AskReplacement(player, ..)
+            | AskPile(player, ..) => Some(player),
+            _ => None,
+        }
+    }
+}
+
+// 指示が不可能だった理由
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum ImpossibleReason {
+    EmptyPile(String), // 指定した名前の山が空
+    NothingToGain,     // 獲得元に条件に合うカードが無い
+}
+
+// 選択肢の番号は元のChooseでの並び順。実行できない選択肢を隠しても番号は変わらない
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct ChoiceInfo {
+    pub index: usize,
+    pub label: ChoiceLabel,
+}
+
+// Ask系の結果に対するプレイヤーの答え
+#[allow(dead_code)]
+pub enum Answer {
+    Cards(Vec<CardInstanceId>), // AskCard / AskTrash / AskDiscard / AskGain / AskReplacement
+    YesNo(bool),                // AskOptional
+    Choice(Vec<usize>),         // AskChoice、ChoiceInfo.index を並べる
+    Order(Vec<CardInstanceId>), // AskOrder、置いた後に上（先頭）に来る順
+    CardName(String),           // AskCardName
+    Player(PlayerId),           // AskPlayer
+    Pile(usize),                // AskPile、山の番号
+}
+
+// 答えを待っている質問。答えの検証に使う
+#[derive(Clone)]
+#[allow(dead_code)]
+pub enum Awaiting {
+    Cards(NumberRange<i32>, Vec<CardInstanceId>),
+    YesNo,
+    Choice(NumberRange<i32>, Vec<(usize, ChoiceOption)>),
+    Order(Vec<CardInstanceId>, bool), // 候補、答えを逆順に置くか
+    CardName(Vec<String>),
+    Player(Vec<PlayerId>),
+    Pile(Vec<usize>),
+}
@@ -0,0 +1,10 @@
+use crate::{core::turn_rule::TurnRule, game::player::PlayerId};
+
+// 予約された追加ターン
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct ExtraTurn {
+    pub player: PlayerId,
+    pub controller: Option<PlayerId>, // 憑依なら、決定を行うプレイヤー
+    pub rules: Vec<TurnRule>,
+}
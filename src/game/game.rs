@@ -1,29 +1,43 @@
 use crate::{
     core::{
-        card::Card,
-        effect::CardEffect::{self, *},
+        card::{Card, CardType},
+        cost::{Cost, CostScope},
+        effect::{
+            ActionKind,
+            CardEffect::{self, *},
+            ChoiceOption, EffectCond, EffectTrigger, GainSource,
+        },
+        history::{HistoryKind, TurnScope},
+        locale::{fill_template, LocaleCatalog, LocaleKey},
         number::{
             Number::{self, *},
             NumberRange::{self, *},
         },
         selector::{
             CardNameSelector::{self, *},
-            CardSelector,
+            CardSelector, PileSelector, PlayerSelector,
         },
+        token,
+        turn_phase::TurnPhase,
+        turn_rule::TurnRule,
         zone::Zone::{self, *},
     },
     game::{
-        card_instance::{CardInstance, CardInstanceId},
+        card_instance::{CardAddress, CardInstance, CardInstanceId, CardInstanceInfo},
+        cost_modifier::CostModifier,
         effect_stack::{
-            EffectStackFrame,
+            Answer, Awaiting, Binding, ChoiceInfo, EffectStackFrame,
             EffectStepResult::{self, *},
+            ImpossibleReason, InternalStep, PendingAction,
         },
+        extra_turn::ExtraTurn,
         player::{PlayerData, PlayerId},
+        supply::{Pile, Supply},
+        turn_history::TurnHistory,
     },
 };
-use rand::Rng;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::{
-    clone,
     collections::{HashMap, VecDeque},
     vec,
 };
@@ -32,27 +46,382 @@ use std::{
 #[allow(dead_code)]
 pub struct Game<'a> {
     pub players: Vec<PlayerData>,
-    pub supply: Vec<Vec<CardInstanceId>>,
+    pub supply: Supply,
     pub trash: Vec<CardInstanceId>,
     pub turn: i32,
+    pub turn_player: PlayerId,
+    pub phase: TurnPhase,
     pub stack: Vec<EffectStackFrame>,
+    pub actions: i32,
+    pub buys: i32,
+    pub coins: i32,
+    pub potions: i32,
+    pub catalog: LocaleCatalog,
+    pub impossible: Vec<ImpossibleReason>, // 不可能だった指示の記録
+    pub history: Vec<TurnHistory>,         // 直前のターンと今のターン（末尾）
+    pub extra_turns: Vec<ExtraTurn>,       // 予約された追加ターン。先頭から行う
+    pub secret_mats: Vec<String>,          // 持ち主しか中身を見られないマットの名前
+    pub card_pool: Vec<&'a Card>,          // 宣言できるカード。ゲームに登場しないものも含む
+    pub pile_token_rules: Vec<(String, EffectTrigger, CardEffect)>, // 山のトークンの名前、反応するイベント、効果
+    pub controller: Option<PlayerId>, // 今のターンを操作しているプレイヤー。憑依されていなければNone
+    turn_rules: Vec<TurnRule>,        // 今のターンの決まり
+    extra_turn: bool,
+    round_player: PlayerId, // 通常の手番順での今の手番。追加ターンの後はこの左隣から
+    set_aside_trash: Vec<CardInstanceId>, // 憑依中に廃棄する代わりに脇に置いたカード
+    played_from_hand: i32,
+    awaiting: Option<Awaiting>,
+    bindings: HashMap<usize, HashMap<String, Binding>>, // 解決の番号ごと
+    choices: HashMap<usize, Vec<(PlayerId, String)>>,   // 解決の番号ごとの、選ばれた選択肢のタグ
+    turn_marks: Vec<(Option<CardInstanceId>, String)>,  // MarkThisTurnの印
+    delayed: Vec<(PlayerId, Option<CardInstanceId>, EffectTrigger, CardEffect)>, // DelayThisTurnで登録された効果
+    cost_modifiers: Vec<CostModifier>,
+    pending: HashMap<usize, PendingAction>, // 置換効果を待っている動作。ApplyReplacementsを積んだ解決の番号ごと
+    attacks: HashMap<usize, bool>, // リアクションの解決を待っている攻撃。無効にされたらtrue
+    next_resolution: usize,
     card_instances: HashMap<CardInstanceId, CardInstance<'a>>,
+    next_instance_id: usize,
+    rng: StdRng,
 }
 
 #[allow(dead_code)]
 impl<'a> Game<'a> {
+    pub fn new(players: Vec<PlayerData>, catalog: LocaleCatalog) -> Self {
+        let turn_player = players.first().map_or_else(|| PlayerId::new(0), |p| p.id);
+        Game {
+            players,
+            supply: Supply::default(),
+            trash: vec![],
+            turn: 0,
+            turn_player,
+            phase: TurnPhase::Action,
+            stack: vec![],
+            actions: 1,
+            buys: 1,
+            coins: 0,
+            potions: 0,
+            catalog,
+            impossible: vec![],
+            history: vec![TurnHistory::new(turn_player)],
+            extra_turns: vec![],
+            secret_mats: vec![],
+            card_pool: vec![],
+            pile_token_rules: vec![],
+            controller: None,
+            turn_rules: vec![],
+            extra_turn: false,
+            round_player: turn_player,
+            set_aside_trash: vec![],
+            played_from_hand: 0,
+            awaiting: None,
+            bindings: HashMap::new(),
+            choices: HashMap::new(),
+            turn_marks: vec![],
+            delayed: vec![],
+            cost_modifiers: vec![],
+            pending: HashMap::new(),
+            attacks: HashMap::new(),
+            next_resolution: 0,
+            card_instances: HashMap::new(),
+            next_instance_id: 0,
+            rng: StdRng::from_os_rng(),
+        }
+    }
+
+    /// 手番を始める。ターン中の印や資源はリセットされる
+    pub fn start_turn(&mut self, player: PlayerId) {
+        self.turn += 1;
+        self.turn_player = player;
+        self.round_player = player;
+        self.controller = None;
+        self.turn_rules.clear();
+        self.extra_turn = false;
+        self.played_from_hand = 0;
+        self.phase = TurnPhase::Action;
+        self.actions = 1;
+        self.buys = 1;
+        self.coins = 0;
+        self.potions = 0;
+        self.turn_marks.clear();
+        self.delayed.clear();
+        self.history.push(TurnHistory::new(player));
+        if self.history.len() > 2 {
+            self.history.remove(0);
+        }
+        self.cost_modifiers
+            .retain(|m| !matches!(m.scope, CostScope::ThisTurn));
+    }
+
+    /// クリーンアップを始める。場と手札のカードを捨て札にしてから next_hand_size 枚引く。処理は run で進める。
+    /// 捨て札にするのは DiscardCard と同じなので、置換効果や「捨て札にしたとき」の能力が働く。
+    /// 場のカードはCleanupの能力で場に残ることがある
+    pub fn cleanup(&mut self, player: PlayerId) {
+        self.phase = TurnPhase::Cleanup;
+        let discard = DiscardCard(CardSelector {
+            name: Any,
+            zone: vec![Play, Hand],
+        });
+        let draw = PlusDraw(Constant(self.next_hand_size()));
+        self.push_effect(player, Sequence(vec![discard, draw]), None);
+    }
+
+    /// 次のターンを始め、その手番のプレイヤーを返す。予約された追加ターンがあれば先に行う
+    pub fn next_turn(&mut self) -> PlayerId {
+        for id in std::mem::take(&mut self.set_aside_trash) {
+            if let Some(CardAddress::PlayerOwned(owner, Aside)) =
+                self.get_card_instance(id).map(|c| c.address.clone())
+            {
+                self.move_card(id, owner, &Discard);
+            }
+        }
+        while !self.extra_turns.is_empty() {
+            let extra = self.extra_turns.remove(0);
+            // 追加ターンに続けて、同じプレイヤーの追加ターンは行わない
+            if self.extra_turn && extra.player == self.turn_player {
+                continue;
+            }
+            let round_player = self.round_player;
+            self.start_turn(extra.player);
+            self.round_player = round_player;
+            self.extra_turn = true;
+            self.controller = extra.controller;
+            self.turn_rules = extra.rules;
+            return extra.player;
+        }
+        let next = self.resolve_players(
+            self.round_player,
+            self.round_player,
+            &PlayerSelector::LeftOfTarget,
+        )[0];
+        self.start_turn(next);
+        next
+    }
+
+    /// クリンナップで引く手札の枚数。次が前哨地の追加ターンなら少なくなる
+    pub fn next_hand_size(&self) -> i32 {
+        self.extra_turns
+            .iter()
+            .find(|t| !(self.extra_turn && t.player == self.turn_player))
+            .filter(|t| t.player == self.turn_player)
+            .and_then(|t| {
+                t.rules.iter().find_map(|r| match r {
+                    TurnRule::HandSize(n) => Some(*n),
+                    _ => None,
+                })
+            })
+            .unwrap_or(5)
+    }
+
+    /// そのプレイヤーの代わりに決定を行うプレイヤー。憑依されたターンなら操作している側
+    pub fn decider(&self, player: PlayerId) -> PlayerId {
+        match self.controller {
+            Some(controller) if player == self.turn_player => controller,
+            _ => player,
+        }
+    }
+
+    /// シャッフルの乱数を固定する（セットアップコードの再現用）
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn new_instance(&mut self, card: &'a Card, address: CardAddress) -> CardInstanceId {
+        let id = CardInstanceId::new(self.next_instance_id);
+        self.next_instance_id += 1;
+        let origin = match address {
+            CardAddress::Supply(pile) => Some(pile),
+            _ => None,
+        };
+        self.card_instances.insert(
+            id,
+            CardInstance {
+                card,
+                id,
+                address,
+                origin,
+            },
+        );
+        id
+    }
+
+    /// カードの実体を作り、プレイヤーのゾーンに置く（ゲーム準備用）
+    pub fn put_card(&mut self, player: PlayerId, zone: Zone, card: &'a Card) -> CardInstanceId {
+        let id = self.new_instance(card, CardAddress::PlayerOwned(player, zone.clone()));
+        if !self.place_card(id, player, &zone) {
+            panic!("put_card: 仮想ゾーンには置けない");
+        }
+        id
+    }
+
+    // プレイヤーの実在のゾーン。Deck と DeckTop はどちらも山札（末尾が一番上）
+    fn zone_mut(&mut self, player: PlayerId, zone: &Zone) -> Option<&mut Vec<CardInstanceId>> {
+        let playerdata = self.get_player_mut(player)?;
+        match zone {
+            Zone::Deck | Zone::DeckTop => Some(&mut playerdata.deck),
+            Zone::Hand => Some(&mut playerdata.hand),
+            Zone::Discard => Some(&mut playerdata.discard),
+            Zone::Play => Some(&mut playerdata.play),
+            Zone::Pending => Some(&mut playerdata.pending),
+            Zone::Aside => Some(&mut playerdata.aside),
+            Zone::Revealed => Some(&mut playerdata.revealed),
+            Zone::Mat(name) => Some(playerdata.mats.entry(name.clone()).or_default()),
+            _ => None,
+        }
+    }
+
+    /// カードをゾーンの末尾（山札なら一番上）に置き、住所を更新する。仮想ゾーンには置けない
+    fn place_card(&mut self, id: CardInstanceId, player: PlayerId, zone: &Zone) -> bool {
+        let Some(cards) = self.zone_mut(player, zone) else {
+            return false;
+        };
+        cards.push(id);
+        let zone = match zone {
+            Zone::DeckTop => Zone::Deck,
+            z => z.clone(),
+        };
+        self.card_instances.get_mut(&id).unwrap().address = CardAddress::PlayerOwned(player, zone);
+        true
+    }
+
+    /// カードを今ある場所から取り除く
+    fn remove_card(&mut self, id: CardInstanceId) {
+        let Some(address) = self.get_card_instance(id).map(|c| c.address.clone()) else {
+            return;
+        };
+        let cards = match &address {
+            CardAddress::PlayerOwned(player, zone) => self.zone_mut(*player, zone),
+            CardAddress::Supply(pile) => self.supply.piles.get_mut(*pile).map(|p| &mut p.cards),
+            CardAddress::Trash => Some(&mut self.trash),
+        };
+        if let Some(cards) = cards {
+            cards.retain(|c| *c != id);
+        }
+        // 場を離れたら、そのカードが場にある間のコスト修正は終わる。もう一度使われても重ならない
+        if matches!(address, CardAddress::PlayerOwned(_, Play)) {
+            self.cost_modifiers
+                .retain(|m| !(matches!(m.scope, CostScope::WhileInPlay) && m.source == Some(id)));
+        }
+    }
+
+    /// カードをプレイヤーのゾーンへ移す
+    pub fn move_card(&mut self, id: CardInstanceId, player: PlayerId, zone: &Zone) -> bool {
+        if self.zone_mut(player, zone).is_none() {
+            return false;
+        }
+        self.remove_card(id);
+        self.place_card(id, player, zone)
+    }
+
+    /// カードを廃棄置き場に置く。能力は誘発しない
+    fn trash_card(&mut self, id: CardInstanceId) {
+        // 憑依されたプレイヤーのカードは、廃棄する代わりにターンの終わりまで脇に置く
+        let owner = match self.get_card_instance(id).map(|c| &c.address) {
+            Some(CardAddress::PlayerOwned(owner, _)) => Some(*owner),
+            _ => None,
+        };
+        if self.controller.is_some() && owner == Some(self.turn_player) {
+            self.move_card(id, self.turn_player, &Aside);
+            self.set_aside_trash.push(id);
+            return;
+        }
+        self.remove_card(id);
+        self.trash.push(id);
+        self.card_instances.get_mut(&id).unwrap().address = CardAddress::Trash;
+    }
+
+    /// 山を作る。cardsは上から順に並べる。山の番号を返す
+    fn add_pile(
+        &mut self,
+        name: &str,
+        cards: &[(&'a Card, i32)],
+        shuffle: bool,
+        in_supply: bool,
+    ) -> usize {
+        let pile = self.supply.len();
+        let mut order: Vec<&'a Card> = cards
+            .iter()
+            .flat_map(|(card, n)| std::iter::repeat_n(*card, (*n).max(0) as usize))
+            .collect();
+        if shuffle {
+            for i in (1..order.len()).rev() {
+                let j = self.rng.random_range(0..=i);
+                order.swap(i, j);
+            }
+        }
+        // 末尾が一番上なので、最後のカードから積む
+        let ids = order
+            .into_iter()
+            .rev()
+            .map(|card| self.new_instance(card, CardAddress::Supply(pile)))
+            .collect();
+        self.supply.piles.push(Pile {
+            name: name.to_owned(),
+            cards: ids,
+            in_supply,
+            tokens: HashMap::new(),
+        });
+        pile
+    }
+
+    /// サプライに新しい山をn枚で作る。山の番号を返す
+    pub fn add_supply_pile(&mut self, card: &'a Card, n: i32) -> usize {
+        self.add_pile(&card.name, &[(card, n)], false, true)
+    }
+
+    /// サプライ外に新しい山をn枚で作る（褒賞、略奪品、馬など）。山の番号を返す
+    pub fn add_non_supply_pile(&mut self, card: &'a Card, n: i32) -> usize {
+        self.add_pile(&card.name, &[(card, n)], false, false)
+    }
+
+    /// 複数のカードからなるサプライの山を作る。野営地／略奪品のような分割山は上から順に、騎士はシャッフルして積む
+    pub fn add_mixed_pile(
+        &mut self,
+        name: &str,
+        cards: &[(&'a Card, i32)],
+        shuffle: bool,
+    ) -> usize {
+        self.add_pile(name, cards, shuffle, true)
+    }
+
+    /// 各プレイヤーの山札をシャッフルする（ゲーム準備用）。DeckShuffledは起きない
+    pub fn shuffle_starting_decks(&mut self) {
+        for p in 0..self.players.len() {
+            let mut deck = std::mem::take(&mut self.players[p].deck);
+            for i in (1..deck.len()).rev() {
+                let j = self.rng.random_range(0..=i);
+                deck.swap(i, j);
+            }
+            self.players[p].deck = deck;
+        }
+    }
+
     fn shuffle(&mut self, player: PlayerId) {
-        let playerdata = self.get_player_mut(player).unwrap();
+        let mut deck = std::mem::take(&mut self.get_player_mut(player).unwrap().deck);
         // Fisher-Yates shuffle
-        for i in (1..playerdata.deck.len()).rev() {
-            let j = rand::rng().random_range(0..=i);
-            playerdata.deck.swap(i, j);
+        for i in (1..deck.len()).rev() {
+            let j = self.rng.random_range(0..=i);
+            deck.swap(i, j);
+        }
+        self.get_player_mut(player).unwrap().deck = deck.clone();
+        self.fire(player, &EffectTrigger::DeckShuffled, vec![]);
+        // 混ぜられたカード自身の能力（隠し財産など）は、プレイヤーのカードの能力より先に解決する
+        for id in deck.into_iter().rev() {
+            for effect in self
+                .own_rules(id, &EffectTrigger::DeckShuffled)
+                .into_iter()
+                .rev()
+            {
+                self.push_effect(player, effect, Some(id));
+                self.stack.last_mut().unwrap().focus = vec![id];
+            }
         }
     }
 
     fn reshuffle(&mut self, player: PlayerId) {
         let playerdata = self.get_player_mut(player).unwrap();
-        playerdata.deck.append(&mut playerdata.discard);
+        let discard = std::mem::take(&mut playerdata.discard);
+        for id in discard {
+            self.place_card(id, player, &Zone::Deck);
+        }
         self.shuffle(player);
     }
 
@@ -103,12 +472,50 @@ impl<'a> Game<'a> {
         self.card_instances.get(&id)
     }
 
-    fn get_card_instances(&self, ids: &Vec<CardInstanceId>) -> Vec<&'_ CardInstance<'a>> {
+    fn get_card_instances(&self, ids: &[CardInstanceId]) -> Vec<&'_ CardInstance<'a>> {
         ids.iter()
             .map(|id| self.get_card_instance(*id).unwrap())
             .collect()
     }
 
+    // 表示用の文字列は、見るプレイヤーのロケールで解決する。カタログに無ければカード定義の名前にフォールバック
+    pub fn localized_card_name(&self, viewer: PlayerId, card: &Card) -> String {
+        let Some(player) = self.get_player(viewer) else {
+            return card.localized_name.clone();
+        };
+        self.catalog
+            .get(player.locale, &LocaleKey::CardName(card.name.clone()))
+            .unwrap_or(&card.localized_name)
+            .to_owned()
+    }
+
+    pub fn localized_type_name(&self, viewer: PlayerId, t: &CardType) -> Option<String> {
+        let player = self.get_player(viewer)?;
+        self.catalog
+            .get(player.locale, &LocaleKey::CardType(t.clone()))
+            .map(str::to_owned)
+    }
+
+    pub fn localized_zone_name(&self, viewer: PlayerId, zone: &Zone) -> Option<String> {
+        let player = self.get_player(viewer)?;
+        self.catalog
+            .get(player.locale, &LocaleKey::Zone(zone.clone()))
+            .map(str::to_owned)
+    }
+
+    /// ログ文を見るプレイヤーのロケールで組み立てる。{player} にはプレイヤー名、{card} には各自のロケールのカード名を渡すこと
+    pub fn localized_log(
+        &self,
+        viewer: PlayerId,
+        key: &str,
+        args: &[(&str, &str)],
+    ) -> Option<String> {
+        let player = self.get_player(viewer)?;
+        self.catalog
+            .get(player.locale, &LocaleKey::Log(key.to_owned()))
+            .map(|template| fill_template(template, args))
+    }
+
     pub fn resolve_number(&self, player: PlayerId, n: &Number) -> i32 {
         match n {
             Constant(n) => *n,
@@ -116,14 +523,193 @@ impl<'a> Game<'a> {
             CountCost(selector) => self
                 .resolve_selector(player, selector)
                 .iter()
-                .map(|c| self.resolve_number(player, &c.card.cost))
+                .map(|c| self.card_cost(player, c.card, c.origin))
                 .sum(),
-            CountEmptyPiles => self.supply.iter().filter(|pile| pile.is_empty()).count() as i32,
+            CountEmptyPiles => self.supply.empty_piles() as i32,
             Plus(a, b) => self.resolve_number(player, a) + self.resolve_number(player, b),
             Minus(a, b) => self.resolve_number(player, a) - self.resolve_number(player, b),
             Times(a, b) => self.resolve_number(player, a) * self.resolve_number(player, b),
             Div(a, b) => self.resolve_number(player, a) / self.resolve_number(player, b),
             Mod(a, b) => self.resolve_number(player, a) % self.resolve_number(player, b),
+            Number::Bound(name) => match self.binding(name) {
+                Some(Binding::Number(n)) => *n,
+                Some(Binding::Cards(ids)) => ids.len() as i32,
+                None => 0,
+            },
+            Min(a, b) => self
+                .resolve_number(player, a)
+                .min(self.resolve_number(player, b)),
+            Max(a, b) => self
+                .resolve_number(player, a)
+                .max(self.resolve_number(player, b)),
+            CountDistinctNames(selector) => {
+                let mut names: Vec<&str> = self
+                    .resolve_selector(player, selector)
+                    .iter()
+                    .map(|c| c.card.name.as_str())
+                    .collect();
+                names.sort();
+                names.dedup();
+                names.len() as i32
+            }
+            CountPile(name) => self.supply.pile(name).map_or(0, |p| p.len() as i32),
+            CountTrash(selector) => self
+                .get_card_instances(&self.trash)
+                .iter()
+                .filter(|c| self.resolve_instance_name(player, selector, c))
+                .count() as i32,
+            PlayerCount => self.players.len() as i32,
+            TreasuresInPlay => self.get_player(player).map_or(0, |p| {
+                self.get_card_instances(&p.play)
+                    .iter()
+                    .filter(|c| c.card.types.contains(&CardType::Treasure))
+                    .count() as i32
+            }),
+            Number::OfPlayer(selector, n) => {
+                let me = self.stack.last().map_or(player, |frame| frame.player);
+                self.resolve_players(me, player, selector)
+                    .into_iter()
+                    .map(|p| self.resolve_number(p, n))
+                    .sum()
+            }
+            CountPileTokens(selector, name) => self
+                .resolve_piles(selector)
+                .iter()
+                .map(|pile| self.supply[*pile].tokens.get(name).copied().unwrap_or(0))
+                .sum(),
+            CountTokens(name) => self.get_player(player).map_or(0, |p| p.token(name)),
+            CountHistory(scope, kind, selector) => {
+                self.count_history(player, *scope, *kind, selector)
+            }
+        }
+    }
+
+    fn count_history(
+        &self,
+        player: PlayerId,
+        scope: TurnScope,
+        kind: HistoryKind,
+        selector: &CardNameSelector,
+    ) -> i32 {
+        let back = match scope {
+            TurnScope::ThisTurn => 1,
+            TurnScope::PreviousTurn => 2,
+        };
+        let Some(history) = self
+            .history
+            .len()
+            .checked_sub(back)
+            .map(|i| &self.history[i])
+        else {
+            return 0;
+        };
+        history
+            .events
+            .iter()
+            .filter(|(k, p, id)| {
+                *k == kind
+                    && *p == player
+                    && self
+                        .get_card_instance(*id)
+                        .is_some_and(|c| self.resolve_instance_name(player, selector, c))
+            })
+            .count() as i32
+    }
+
+    // 今のターンの履歴に出来事を足す
+    fn record(&mut self, kind: HistoryKind, player: PlayerId, ids: &[CardInstanceId]) {
+        if let Some(history) = self.history.last_mut() {
+            history
+                .events
+                .extend(ids.iter().map(|id| (kind, player, *id)));
+        }
+    }
+
+    /// 該当する山の番号
+    fn resolve_piles(&self, selector: &PileSelector) -> Vec<usize> {
+        match selector {
+            PileSelector::Named(name) => self.supply.position(name).into_iter().collect(),
+            PileSelector::OfFocused => self
+                .stack
+                .last()
+                .and_then(|f| f.focus.first())
+                .and_then(|id| self.get_card_instance(*id))
+                .and_then(|c| c.origin)
+                .into_iter()
+                .collect(),
+            PileSelector::Chosen => self.stack.last().and_then(|f| f.pile).into_iter().collect(),
+            PileSelector::All => (0..self.supply.len())
+                .filter(|pile| self.supply[*pile].in_supply)
+                .collect(),
+        }
+    }
+
+    fn named_card(&self) -> Option<&str> {
+        self.stack.last()?.named_card.as_deref()
+    }
+
+    // 名前で引けるカード。登録されているカードと、このゲームに登場するカード
+    fn known_card(&self, name: &str) -> Option<&'a Card> {
+        self.card_pool
+            .iter()
+            .copied()
+            .chain(self.card_instances.values().map(|c| c.card))
+            .find(|card| card.name == name)
+    }
+
+    pub fn resolve_cond(&self, player: PlayerId, cond: &EffectCond) -> bool {
+        match cond {
+            EffectCond::Leq(a, b) => {
+                self.resolve_number(player, a) <= self.resolve_number(player, b)
+            }
+            EffectCond::Geq(a, b) => {
+                self.resolve_number(player, a) >= self.resolve_number(player, b)
+            }
+            EffectCond::Eq(a, b) => {
+                self.resolve_number(player, a) == self.resolve_number(player, b)
+            }
+            EffectCond::CondAnd(conds) => conds.iter().all(|c| self.resolve_cond(player, c)),
+            EffectCond::CondOr(conds) => conds.iter().any(|c| self.resolve_cond(player, c)),
+            EffectCond::CondNot(c) => !self.resolve_cond(player, c),
+            EffectCond::NamedIs(selector) => self
+                .named_card()
+                .and_then(|name| self.known_card(name))
+                .is_some_and(|card| self.resolve_name(player, selector, card)),
+            EffectCond::MyTurn => self.turn_player == player,
+            EffectCond::PhaseIs(phase) => self.phase == *phase,
+            EffectCond::ThisInPlay => self
+                .resolve_zone(player, &Itself)
+                .iter()
+                .any(|c| matches!(c.address, CardAddress::PlayerOwned(_, Play))),
+            EffectCond::FocusedIs(selector) => {
+                let focused = self.resolve_zone(player, &Focused);
+                !focused.is_empty()
+                    && focused
+                        .iter()
+                        .all(|c| self.resolve_instance_name(player, selector, c))
+            }
+            EffectCond::PileEmpty(name) => self.supply.pile(name).is_some_and(|p| p.is_empty()),
+            EffectCond::FirstTimeThisTurn(tag) => {
+                let cause = self.stack.last().and_then(|frame| frame.cause);
+                !self.turn_marks.iter().any(|(c, t)| *c == cause && t == tag)
+            }
+            EffectCond::Happened(scope, kind, selector) => {
+                self.count_history(player, *scope, *kind, selector) > 0
+            }
+            EffectCond::PileHasToken(selector, name) => self
+                .resolve_piles(selector)
+                .iter()
+                .any(|pile| self.supply[*pile].tokens.get(name).is_some_and(|n| *n > 0)),
+            EffectCond::PreviousTurnMine => self
+                .history
+                .len()
+                .checked_sub(2)
+                .is_some_and(|i| self.history[i].turn_player == player),
+            EffectCond::OpponentChose(tag) => self.stack.last().is_some_and(|frame| {
+                self.choices.get(&frame.resolution).is_some_and(|choices| {
+                    choices.iter().any(|(p, t)| *p != frame.player && t == tag)
+                })
+            }),
         }
     }
 
@@ -145,24 +731,463 @@ impl<'a> Game<'a> {
     }
 
     pub fn resolve_name(&self, player: PlayerId, selector: &CardNameSelector, card: &Card) -> bool {
+        self.match_name(player, selector, card, None, true)
+    }
+
+    /// カードの実体について判定する。作られた山ごとのコスト修正も反映する
+    pub fn resolve_instance_name(
+        &self,
+        player: PlayerId,
+        selector: &CardNameSelector,
+        instance: &CardInstance<'a>,
+    ) -> bool {
+        self.match_name(player, selector, instance.card, instance.origin, true)
+    }
+
+    // modified が偽なら、コストを修正前の値で比べる（コスト修正の対象を決めるときに使う）
+    // origin はカードが作られたサプライの山の番号。実体の無いカードならNone
+    fn match_name(
+        &self,
+        player: PlayerId,
+        selector: &CardNameSelector,
+        card: &Card,
+        origin: Option<usize>,
+        modified: bool,
+    ) -> bool {
         match selector {
             Name(name) => card.name == *name,
-            NameAnd(selectors) => selectors.iter().all(|s| self.resolve_name(player, s, card)),
-            NameOr(selectors) => selectors.iter().any(|s| self.resolve_name(player, s, card)),
-            NameNot(selector) => !self.resolve_name(player, selector, card),
+            NameAnd(selectors) => selectors
+                .iter()
+                .all(|s| self.match_name(player, s, card, origin, modified)),
+            NameOr(selectors) => selectors
+                .iter()
+                .any(|s| self.match_name(player, s, card, origin, modified)),
+            NameNot(selector) => !self.match_name(player, selector, card, origin, modified),
             HasType(t) => card.types.contains(t),
-            Cost(n) => self
-                .resolve_number_range(player, n)
-                .contains(self.resolve_number(player, &card.cost)),
+            Cost(n) => {
+                let cost = self.full_cost(player, card, origin, modified);
+                let range = self.resolve_number_range(player, n);
+                // 「$4以下」に、ポーションや負債のあるカードは含まれない
+                range.contains(cost.coin)
+                    && (matches!(range, AtLeast(_) | AnyNumber)
+                        || (cost.potion == 0 && cost.debt == 0))
+            }
+            CostUpTo(selector, n) => {
+                self.resolve_selector(player, selector)
+                    .first()
+                    .is_some_and(|c| {
+                        let limit = self
+                            .full_cost(player, c.card, c.origin, modified)
+                            .plus_coin(self.resolve_number(player, n));
+                        self.full_cost(player, card, origin, modified).leq(&limit)
+                    })
+            }
+            CostLessThan(selector) => {
+                self.resolve_selector(player, selector)
+                    .first()
+                    .is_some_and(|c| {
+                        let other = self.full_cost(player, c.card, c.origin, modified);
+                        self.full_cost(player, card, origin, modified)
+                            .less_than(&other)
+                    })
+            }
+            Named => self.named_card().is_some_and(|name| card.name == name),
             Any => true,
         }
     }
 
+    fn cost_modifier_active(&self, modifier: &CostModifier, origin: Option<usize>) -> bool {
+        match &modifier.scope {
+            CostScope::ThisTurn => true,
+            CostScope::WhileInPlay => modifier.source.is_some_and(|id| {
+                self.get_card_instance(id)
+                    .is_some_and(|c| matches!(c.address, CardAddress::PlayerOwned(_, Play)))
+            }),
+            CostScope::Pile(name) => origin.is_some() && origin == self.supply.position(name),
+        }
+    }
+
+    /// ポーションと負債も含めたコスト。modifiedなら金に修正を適用する
+    pub fn full_cost(
+        &self,
+        player: PlayerId,
+        card: &Card,
+        origin: Option<usize>,
+        modified: bool,
+    ) -> Cost {
+        let coin = if modified {
+            self.card_cost(player, card, origin)
+        } else {
+            self.resolve_number(player, &card.cost.coin)
+        };
+        Cost {
+            coin,
+            potion: card.cost.potion,
+            debt: card.cost.debt,
+        }
+    }
+
+    /// 修正を全部適用した今の金のコスト。0未満にはならない。originはカードが作られた山の番号
+    pub fn card_cost(&self, player: PlayerId, card: &Card, origin: Option<usize>) -> i32 {
+        let base = self.resolve_number(player, &card.cost.coin);
+        let modifier: i32 = self
+            .cost_modifiers
+            .iter()
+            .filter(|m| {
+                self.cost_modifier_active(m, origin)
+                    && self.match_name(player, &m.selector, card, origin, false)
+            })
+            .map(|m| m.amount)
+            .sum();
+        // 公道のような常時の能力
+        let continuous: i32 = self
+            .active_rules(None, &EffectTrigger::Continuous)
+            .iter()
+            .map(|(_, effect)| match effect {
+                ModifyCost(_, selector, n)
+                    if self.match_name(player, selector, card, origin, false) =>
+                {
+                    self.resolve_number(player, n)
+                }
+                _ => 0,
+            })
+            .sum();
+        (base + modifier + continuous).max(0)
+    }
+
+    fn in_zone(&self, instance: &CardInstance<'a>, zone: &Zone) -> bool {
+        match (&instance.address, zone) {
+            (CardAddress::PlayerOwned(owner, Deck), DeckTop) => self
+                .get_player(*owner)
+                .is_some_and(|p| p.deck.last() == Some(&instance.id)),
+            (CardAddress::PlayerOwned(_, z), zone) => z == zone,
+            (CardAddress::Supply(pile), Zone::Supply) => self.supply[*pile].in_supply,
+            (CardAddress::Supply(pile), Zone::SupplyPile(name)) => self.supply[*pile].name == *name,
+            (CardAddress::Trash, Zone::Trash) => true,
+            _ => false,
+        }
+    }
+
+    // WhileIn で書かれた能力が、今のカードの位置で有効か
+    fn rule_active(
+        &self,
+        instance: &CardInstance<'a>,
+        trigger: &EffectTrigger,
+        event: &EffectTrigger,
+    ) -> bool {
+        match trigger {
+            EffectTrigger::WhileIn(zone, inner) => {
+                self.in_zone(instance, zone)
+                    && (**inner == *event || self.rule_active(instance, inner, event))
+            }
+            _ => false,
+        }
+    }
+
+    /// そのイベントで有効な、場などにあるカードの能力。playerを指定すればそのプレイヤーのカードだけ
+    fn active_rules(
+        &self,
+        player: Option<PlayerId>,
+        event: &EffectTrigger,
+    ) -> Vec<(CardInstanceId, CardEffect)> {
+        let mut instances: Vec<&CardInstance<'a>> = self
+            .card_instances
+            .values()
+            .filter(|c| match (player, &c.address) {
+                (None, _) => true,
+                (Some(p), CardAddress::PlayerOwned(owner, _)) => *owner == p,
+                _ => false,
+            })
+            .collect();
+        instances.sort_by_key(|c| c.id);
+        instances
+            .into_iter()
+            .flat_map(|c| {
+                c.card
+                    .rules
+                    .iter()
+                    .filter(|(trigger, _)| self.rule_active(c, trigger, event))
+                    .map(|(_, effect)| (c.id, effect.clone()))
+            })
+            .collect()
+    }
+
+    /// イベントに反応する能力をすべて積む。最初のカードの能力が一番上に来て、DelayThisTurnの効果はその後に登録順で解決される
+    fn fire(&mut self, player: PlayerId, event: &EffectTrigger, focus: Vec<CardInstanceId>) {
+        let delayed: Vec<(Option<CardInstanceId>, CardEffect)> = self
+            .delayed
+            .iter()
+            .filter(|(p, _, trigger, _)| *p == player && trigger == event)
+            .map(|(_, cause, _, effect)| (*cause, effect.clone()))
+            .collect();
+        let rules = self
+            .active_rules(Some(player), event)
+            .into_iter()
+            .map(|(id, effect)| (Some(id), effect));
+        for (cause, effect) in rules.chain(delayed).rev() {
+            self.push_effect(player, effect, cause);
+            self.stack.last_mut().unwrap().focus = focus.clone();
+        }
+    }
+
+    /// カードが動いた後に呼ぶ。1枚ずつ、動いたカード自身の能力、プレイヤーのカードの能力の順に解決される。
+    /// まとめて動いたときは、全部動かし終えてから最初のカードの分から解決する
+    fn fire_moved(&mut self, player: PlayerId, event: &EffectTrigger, ids: &[CardInstanceId]) {
+        let kind = match event {
+            EffectTrigger::CardGained => Some(HistoryKind::Gained),
+            EffectTrigger::CardBought => Some(HistoryKind::Bought),
+            EffectTrigger::CardTrashed => Some(HistoryKind::Trashed),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            self.record(kind, player, ids);
+        }
+        for id in ids.iter().rev() {
+            self.fire(player, event, vec![*id]);
+            for effect in self.own_rules(*id, event).into_iter().rev() {
+                self.push_effect(player, effect, Some(*id));
+                self.stack.last_mut().unwrap().focus = vec![*id];
+            }
+            // 元の山に置かれたトークンの効果は、カードの能力より先に解決する
+            let Some(pile) = self.get_card_instance(*id).and_then(|c| c.origin) else {
+                continue;
+            };
+            for effect in self.pile_rules(pile, event).into_iter().rev() {
+                self.push_effect(player, effect, None);
+                self.stack.last_mut().unwrap().focus = vec![*id];
+            }
+        }
+    }
+
+    // 山に置かれているトークンの効果
+    fn pile_rules(&self, pile: usize, event: &EffectTrigger) -> Vec<CardEffect> {
+        self.pile_token_rules
+            .iter()
+            .filter(|(name, trigger, _)| {
+                trigger == event && self.supply[pile].tokens.get(name).is_some_and(|n| *n > 0)
+            })
+            .map(|(_, _, effect)| effect.clone())
+            .collect()
+    }
+
+    // ThisCard で書かれた、そのカード自身の能力
+    fn own_rules(&self, id: CardInstanceId, event: &EffectTrigger) -> Vec<CardEffect> {
+        let Some(card) = self.get_card_instance(id).map(|c| c.card) else {
+            return vec![];
+        };
+        card.rules
+            .iter()
+            .filter(|(trigger, _)| {
+                matches!(trigger, EffectTrigger::ThisCard(inner) if **inner == *event)
+            })
+            .map(|(_, effect)| effect.clone())
+            .collect()
+    }
+
+    /// カードが動く前に適用される置換効果。プレイヤーのカードの能力、動くカード自身の能力の順
+    fn replacements(
+        &self,
+        kind: ActionKind,
+        player: PlayerId,
+        id: CardInstanceId,
+    ) -> Vec<(CardInstanceId, CardEffect)> {
+        let event = EffectTrigger::Replace(kind);
+        let mut replacements = self.active_rules(Some(player), &event);
+        replacements.extend(self.own_rules(id, &event).into_iter().map(|e| (id, e)));
+        // クリーンアップで場から捨て札にするときは、そのカード自身のCleanupの能力も置換効果になる
+        let in_play = self
+            .get_card_instance(id)
+            .is_some_and(|c| matches!(c.address, CardAddress::PlayerOwned(_, Play)));
+        if kind == ActionKind::Discard && self.phase == TurnPhase::Cleanup && in_play {
+            let cleanup = self.own_rules(id, &EffectTrigger::Cleanup);
+            replacements.extend(cleanup.into_iter().map(|e| (id, e)));
+        }
+        replacements
+    }
+
+    /// 置換効果があれば動作を保留し、置換効果の解決を積んで true を返す。無ければ何もしない
+    fn defer_replaced(
+        &mut self,
+        kind: ActionKind,
+        player: PlayerId,
+        id: CardInstanceId,
+        zone: &Zone,
+    ) -> bool {
+        let replacements = self.replacements(kind, player, id);
+        if replacements.is_empty() {
+            return false;
+        }
+        let resolution = self.next_resolution;
+        self.push_step(player, InternalStep::ApplyReplacements(resolution));
+        self.pending.insert(
+            resolution,
+            PendingAction {
+                kind,
+                player,
+                card: id,
+                zone: zone.clone(),
+                cancelled: false,
+                redirected: false,
+                replacements,
+            },
+        );
+        true
+    }
+
+    // 置換効果を適用し終えた動作を行う。行き先を変えられた廃棄や捨て札は、廃棄や捨て札として扱わない
+    fn finish_action(&mut self, action: PendingAction) {
+        if action.cancelled {
+            return;
+        }
+        let moved = match &action.zone {
+            Zone::Trash => {
+                self.trash_card(action.card);
+                true
+            }
+            zone => self.move_card(action.card, action.player, zone),
+        };
+        let event = match action.kind {
+            ActionKind::Gain => Some(EffectTrigger::CardGained),
+            ActionKind::Trash if !action.redirected => Some(EffectTrigger::CardTrashed),
+            ActionKind::Discard if !action.redirected => Some(EffectTrigger::CardDiscarded),
+            _ => None,
+        };
+        if let (true, Some(event)) = (moved, event) {
+            self.fire_moved(action.player, &event, &[action.card]);
+        }
+    }
+
+    /// カードを使う。場に出して使ったことを記録し、使用時の効果と、使ったときの能力を積む
+    /// 航海の追加ターンで手札から使える枚数を超えるなら使えない
+    pub fn play_card(&mut self, player: PlayerId, id: CardInstanceId) -> bool {
+        let Some(instance) = self.get_card_instance(id) else {
+            return false;
+        };
+        let (card, address) = (instance.card, instance.address.clone());
+        if matches!(address, CardAddress::PlayerOwned(_, Hand)) {
+            let limit = self.turn_rules.iter().find_map(|r| match r {
+                TurnRule::PlayFromHand(n) => Some(*n),
+                _ => None,
+            });
+            if limit.is_some_and(|n| self.played_from_hand >= n) {
+                return false;
+            }
+            self.played_from_hand += 1;
+        }
+        // 玉座の間で2回目に使うときは、もう場にある
+        if !matches!(address, CardAddress::PlayerOwned(_, Play)) {
+            self.move_card(id, player, &Play);
+        }
+        self.record(HistoryKind::Played, player, &[id]);
+        for (trigger, effect) in card.rules.iter().rev() {
+            if matches!(
+                trigger,
+                EffectTrigger::PlayAsAction | EffectTrigger::PlayAsTreasure
+            ) {
+                self.push_effect(player, effect.clone(), Some(id));
+            }
+        }
+        self.fire(player, &EffectTrigger::CardPlayed, vec![id]);
+        true
+    }
+
+    /// カードを獲得する。獲得時の能力が積まれる
+    pub fn gain_cards(&mut self, player: PlayerId, ids: &[CardInstanceId], zone: &Zone) -> bool {
+        // 憑依されたプレイヤーが獲得するカードは、操作している側が代わりに獲得する
+        let player = self.decider(player);
+        let mut gained = vec![];
+        let mut deferred = 0;
+        for id in ids {
+            if self.defer_replaced(ActionKind::Gain, player, *id, zone) {
+                deferred += 1;
+            } else if self.move_card(*id, player, zone) {
+                gained.push(*id);
+            }
+        }
+        self.fire_moved(player, &EffectTrigger::CardGained, &gained);
+        gained.len() + deferred == ids.len()
+    }
+
+    /// サプライの山から1枚購入する
+    pub fn buy(&mut self, player: PlayerId, pile: usize) -> Result<CardInstanceId, String> {
+        let Some(pile) = self.supply.get(pile).filter(|p| p.in_supply) else {
+            return Err("サプライの山ではない".to_owned());
+        };
+        let Some(id) = pile.top() else {
+            return Err("山が空".to_owned());
+        };
+        let instance = &self.card_instances[&id];
+        let cost = self.full_cost(player, instance.card, instance.origin, true);
+        if self.turn_rules.contains(&TurnRule::NoBuy) {
+            return Err("このターンは購入できない".to_owned());
+        }
+        if self.buys < 1 {
+            return Err("購入権が無い".to_owned());
+        }
+        if self
+            .get_player(player)
+            .is_some_and(|p| p.token(token::DEBT) > 0)
+        {
+            return Err("負債を返すまで購入できない".to_owned());
+        }
+        if self.coins < cost.coin {
+            return Err(format!("{}金が必要", cost.coin));
+        }
+        if self.potions < cost.potion {
+            return Err("ポーションが必要".to_owned());
+        }
+        self.buys -= 1;
+        self.coins -= cost.coin;
+        self.potions -= cost.potion;
+        // 負債のコストは、買ったときに負債トークンとして受け取る
+        if let Some(playerdata) = self.get_player_mut(player) {
+            *playerdata.tokens.entry(token::DEBT.to_owned()).or_default() += cost.debt;
+        }
+        // 購入時の能力を獲得時の能力より先に解決する
+        self.gain_cards(player, &[id], &Discard);
+        self.fire_moved(player, &EffectTrigger::CardBought, &[id]);
+        Ok(id)
+    }
+
     pub fn calculate_vp(&self, player: PlayerId) -> i32 {
+        let tokens = self.get_player(player).map_or(0, |p| p.token(token::VP));
         self.resolve_zone(player, &Zone::AllMyCards)
             .iter()
             .map(|c| self.resolve_number(player, &c.card.vp))
-            .sum()
+            .sum::<i32>()
+            + tokens
+    }
+
+    fn is_secret(&self, zone: &Zone) -> bool {
+        matches!(zone, Zone::Mat(name) if self.secret_mats.contains(name))
+    }
+
+    /// viewerがownerのマットの中身を見られるか
+    pub fn can_see_mat(&self, viewer: PlayerId, owner: PlayerId, mat: &str) -> bool {
+        viewer == owner || !self.secret_mats.iter().any(|m| m == mat)
+    }
+
+    /// トークンを使う。財源は+1金、村人は+1アクションに換える
+    pub fn spend_tokens(&mut self, player: PlayerId, name: &str, n: i32) -> Result<(), String> {
+        let Some(playerdata) = self.get_player_mut(player) else {
+            return Err("プレイヤーがいない".to_owned());
+        };
+        if n < 0 || playerdata.token(name) < n {
+            return Err(format!("{name}が{n}個無い"));
+        }
+        // 負債は金を払って返す
+        if name == token::DEBT && self.coins < n {
+            return Err(format!("{n}金が必要"));
+        }
+        let playerdata = self.get_player_mut(player).unwrap();
+        *playerdata.tokens.entry(name.to_owned()).or_default() -= n;
+        match name {
+            token::COFFERS => self.coins += n,
+            token::VILLAGERS => self.actions += n,
+            token::DEBT => self.coins -= n,
+            _ => {}
+        }
+        Ok(())
     }
 
     pub fn resolve_zone(&self, playerid: PlayerId, zone: &Zone) -> Vec<&'_ CardInstance<'a>> {
@@ -177,31 +1202,61 @@ impl<'a> Game<'a> {
             Zone::Pending => self.get_card_instances(&player.pending),
             Zone::Aside => self.get_card_instances(&player.aside),
             Zone::Revealed => self.get_card_instances(&player.revealed),
-            Zone::DeckTop => {
-                self.get_card_instances(&player.deck.last().into_iter().copied().collect())
-            }
-            Zone::AllMyCards => self.get_card_instances(
-                &[
-                    &player.deck,
-                    &player.hand,
-                    &player.discard,
-                    &player.play,
-                    &player.pending,
-                    &player.aside,
-                    &player.revealed,
-                ]
-                .iter()
-                .flat_map(|v| v.iter().copied())
-                .collect(),
-            ),
+            Zone::Mat(name) => player
+                .mats
+                .get(name)
+                .map_or(vec![], |cards| self.get_card_instances(cards)),
+            Zone::DeckTop => self
+                .get_card_instances(&player.deck.last().into_iter().copied().collect::<Vec<_>>()),
+            Zone::AllMyCards => {
+                let mut mats: Vec<(&String, &Vec<CardInstanceId>)> = player.mats.iter().collect();
+                mats.sort_by_key(|(name, _)| *name);
+                self.get_card_instances(
+                    &[
+                        &player.deck,
+                        &player.hand,
+                        &player.discard,
+                        &player.play,
+                        &player.pending,
+                        &player.aside,
+                        &player.revealed,
+                    ]
+                    .into_iter()
+                    .chain(mats.into_iter().map(|(_, cards)| cards))
+                    .flat_map(|v| v.iter().copied())
+                    .collect::<Vec<_>>(),
+                )
+            }
             Zone::Focused => self
                 .stack
                 .last()
                 .map_or(vec![], |frame| self.get_card_instances(&frame.focus)),
             Zone::Itself => self.stack.last().map_or(vec![], |frame| {
-                self.get_card_instances(&frame.cause.into_iter().collect())
+                self.get_card_instances(frame.cause.as_slice())
             }),
-            _ => vec![],
+            Zone::Bound(name) => match self.binding(name) {
+                Some(Binding::Cards(ids)) => self.get_card_instances(ids),
+                _ => vec![],
+            },
+            Zone::Supply => self.get_card_instances(&self.supply.tops(true)),
+            Zone::SupplyPile(name) => self.get_card_instances(
+                &self
+                    .supply
+                    .pile(name)
+                    .and_then(|p| p.top())
+                    .into_iter()
+                    .collect::<Vec<_>>(),
+            ),
+            Zone::Trash => self.get_card_instances(&self.trash),
+            Zone::OfPlayer(selector, zone) => {
+                let me = self.stack.last().map_or(playerid, |frame| frame.player);
+                // 他のプレイヤーの秘密のマットは選べない
+                self.resolve_players(me, playerid, selector)
+                    .into_iter()
+                    .filter(|p| *p == me || !self.is_secret(zone))
+                    .flat_map(|p| self.resolve_zone(p, zone))
+                    .collect()
+            }
         }
     }
 
@@ -214,31 +1269,304 @@ impl<'a> Game<'a> {
             .zone
             .iter()
             .flat_map(|zone| self.resolve_zone(target, zone))
-            .filter(|c: &&CardInstance<'a>| self.resolve_name(target, &selector.name, c.card))
+            .filter(|c: &&CardInstance<'a>| self.resolve_instance_name(target, &selector.name, c))
             .collect()
     }
 
+    /// 該当するプレイヤーを手番順に並べる。Opponents と AllPlayers は効果を使ったプレイヤーの左隣から
+    pub fn resolve_players(
+        &self,
+        player: PlayerId,
+        target: PlayerId,
+        selector: &PlayerSelector,
+    ) -> Vec<PlayerId> {
+        let n = self.players.len();
+        let position = |id: PlayerId| self.players.iter().position(|p| p.id == id).unwrap_or(0);
+        let nth = |i: usize| self.players[i % n].id;
+        let me = position(player);
+        let target_position = position(target);
+        match selector {
+            PlayerSelector::Me => vec![player],
+            PlayerSelector::Target => vec![target],
+            PlayerSelector::Opponents => (1..n).map(|i| nth(me + i)).collect(),
+            PlayerSelector::AllPlayers => (0..n).map(|i| nth(me + i)).collect(),
+            PlayerSelector::LeftOfTarget => vec![nth(target_position + 1)],
+            PlayerSelector::RightOfTarget => vec![nth(target_position + n - 1)],
+        }
+    }
+
+    /// 効果の解決を始める。実際の処理は run で進める
+    pub fn push_effect(
+        &mut self,
+        player: PlayerId,
+        effect: CardEffect,
+        cause: Option<CardInstanceId>,
+    ) {
+        self.stack.push(EffectStackFrame {
+            player,
+            target: player,
+            effect_queue: VecDeque::from(vec![effect]),
+            focus: vec![],
+            named_card: None,
+            cause,
+            atomic: false,
+            resolution: self.next_resolution,
+            replacing: None,
+            pile: None,
+            internal: None,
+        });
+        self.next_resolution += 1;
+    }
+
+    /// エンジン内部の処理だけを持つフレームを積む
+    fn push_step(&mut self, player: PlayerId, step: InternalStep) {
+        self.push_effect(player, Noop, None);
+        let top = self.stack.last_mut().unwrap();
+        top.effect_queue.clear();
+        top.internal = Some(step);
+    }
+
+    /// 一番上のフレームを捨てる。その解決のフレームが無くなったら名前も捨てる
+    fn pop_frame(&mut self) {
+        let Some(frame) = self.stack.pop() else {
+            return;
+        };
+        if !self.stack.iter().any(|f| f.resolution == frame.resolution) {
+            self.bindings.remove(&frame.resolution);
+            self.choices.remove(&frame.resolution);
+            self.pending.remove(&frame.resolution);
+        }
+    }
+
+    fn binding(&self, name: &str) -> Option<&Binding> {
+        let frame = self.stack.last()?;
+        self.bindings.get(&frame.resolution)?.get(name)
+    }
+
+    /// プレイヤーの答えが必要になるか、スタックが空になるまで効果を解決する
+    pub fn run(&mut self) -> EffectStepResult {
+        if self.awaiting.is_some() {
+            return Error("答えを待っている".to_owned());
+        }
+        loop {
+            match self.pop_and_step() {
+                Continue => {}
+                Impossible(reason) => {
+                    self.impossible.push(reason);
+                    if self.stack.last().is_some_and(|frame| frame.atomic) {
+                        self.pop_frame();
+                    }
+                }
+                SkipContinue => {
+                    // 不可能な指示を含むAtomicSequenceは、残りもまとめて飛ばす
+                    if self.stack.last().is_some_and(|frame| frame.atomic) {
+                        self.pop_frame();
+                    }
+                }
+                mut result => {
+                    if let Some(player) = result.asked_player_mut() {
+                        *player = self.decider(*player);
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// 直前のAsk系の結果に答える。答えが不正なら質問はそのまま残る
+    pub fn answer(&mut self, answer: Answer) -> Result<(), String> {
+        let Some(awaiting) = self.awaiting.take() else {
+            return Err("質問していない".to_owned());
+        };
+        let result = match (&awaiting, answer) {
+            (Awaiting::Cards(range, candidates), Answer::Cards(ids)) => {
+                if !range.contains(ids.len() as i32) {
+                    Err(format!("{}枚は選べない", ids.len()))
+                } else if ids.iter().any(|id| !candidates.contains(id)) {
+                    Err("候補に無いカードを選んだ".to_owned())
+                } else if (1..ids.len()).any(|i| ids[..i].contains(&ids[i])) {
+                    Err("同じカードを2回選んだ".to_owned())
+                } else {
+                    self.stack.last_mut().unwrap().focus = ids;
+                    Ok(())
+                }
+            }
+            (Awaiting::YesNo, Answer::YesNo(yes)) => {
+                if !yes {
+                    self.pop_frame();
+                }
+                Ok(())
+            }
+            (Awaiting::Choice(range, options), Answer::Choice(indices)) => {
+                if !range.contains(indices.len() as i32) {
+                    Err(format!("{}個は選べない", indices.len()))
+                } else if indices.iter().any(|i| !options.iter().any(|(j, _)| i == j)) {
+                    Err("選べない選択肢を選んだ".to_owned())
+                } else if (1..indices.len()).any(|i| indices[..i].contains(&indices[i])) {
+                    Err("同じ選択肢を2回選んだ".to_owned())
+                } else {
+                    let chosen: Vec<&ChoiceOption> = options
+                        .iter()
+                        .filter(|(i, _)| indices.contains(i))
+                        .map(|(_, o)| o)
+                        .collect();
+                    let frame = self.stack.last_mut().unwrap();
+                    // 選んだ順ではなく、カードに書かれた順に実行する
+                    frame.effect_queue = chosen.iter().map(|o| o.effect.clone()).collect();
+                    let (resolution, chooser) = (frame.resolution, frame.target);
+                    self.choices
+                        .entry(resolution)
+                        .or_default()
+                        .extend(chosen.iter().map(|o| (chooser, o.label.tag().to_owned())));
+                    Ok(())
+                }
+            }
+            (Awaiting::Order(candidates, reverse), Answer::Order(mut ids)) => {
+                let mut sorted = ids.clone();
+                sorted.sort_by_key(|id| candidates.iter().position(|c| c == id));
+                if sorted != *candidates {
+                    Err("候補の並べ替えになっていない".to_owned())
+                } else {
+                    if *reverse {
+                        ids.reverse();
+                    }
+                    self.stack.last_mut().unwrap().focus = ids;
+                    Ok(())
+                }
+            }
+            (Awaiting::Player(candidates), Answer::Player(player)) => {
+                if candidates.contains(&player) {
+                    self.stack.last_mut().unwrap().target = player;
+                    Ok(())
+                } else {
+                    Err("選べないプレイヤーを選んだ".to_owned())
+                }
+            }
+            (Awaiting::Pile(candidates), Answer::Pile(pile)) => {
+                if candidates.contains(&pile) {
+                    self.stack.last_mut().unwrap().pile = Some(pile);
+                    Ok(())
+                } else {
+                    Err("選べない山を選んだ".to_owned())
+                }
+            }
+            (Awaiting::CardName(candidates), Answer::CardName(name)) => {
+                if candidates.contains(&name) {
+                    self.stack.last_mut().unwrap().named_card = Some(name);
+                    Ok(())
+                } else {
+                    Err(format!("{name}は宣言できない"))
+                }
+            }
+            _ => Err("答えの種類が違う".to_owned()),
+        };
+        if result.is_err() {
+            self.awaiting = Some(awaiting);
+        }
+        result
+    }
+
     fn pop_and_step(&mut self) -> EffectStepResult {
-        let Some(mut frame) = self.stack.pop() else {
+        let Some(frame) = self.stack.last_mut() else {
             return End;
         };
 
-        let Some(effect) = frame.effect_queue.clone().pop_front() else {
-            self.stack.pop();
+        let Some(effect) = frame.effect_queue.pop_front() else {
+            if let Some(step) = frame.internal.take() {
+                let clone = frame.clone();
+                return self.exec_internal(clone, step);
+            }
+            self.pop_frame();
             return Continue;
         };
 
         let clone = frame.clone();
-        self.stack.push(frame);
         self.exec_effect_one(clone, effect)
     }
 
+    // 残りの効果より先に実行する
     fn extend_frame(&mut self, effects: &[CardEffect]) {
         let Some(frame) = self.stack.last_mut() else {
             return;
         };
 
-        frame.effect_queue.extend(effects.to_owned());
+        for effect in effects.iter().rev() {
+            frame.effect_queue.push_front(effect.clone());
+        }
+    }
+
+    /// 1枚ずつ引く。置換効果のあるカードに当たったら、その解決を待ってから残りを引く
+    fn draw(&mut self, player: PlayerId, n: i32) -> Vec<CardInstanceId> {
+        let mut drawn = vec![];
+        for i in 0..n.max(0) {
+            let playerdata = self.get_player(player).unwrap();
+            if playerdata.deck.is_empty() {
+                if playerdata.discard.is_empty() {
+                    break;
+                }
+                self.reshuffle(player);
+            }
+            let id = *self.get_player(player).unwrap().deck.last().unwrap();
+            if !self.replacements(ActionKind::Draw, player, id).is_empty() {
+                let rest = n - i - 1;
+                if rest > 0 {
+                    self.push_effect(player, PlusDraw(Constant(rest)), None);
+                }
+                self.defer_replaced(ActionKind::Draw, player, id, &Zone::Hand);
+                break;
+            }
+            self.move_card(id, player, &Zone::Hand);
+            drawn.push(id);
+        }
+        drawn
+    }
+
+    // カードを選ばせる質問の候補を作り、答えを待つ状態にする
+    fn card_question(
+        &mut self,
+        target: PlayerId,
+        n: &NumberRange<Number>,
+        selector: &CardSelector,
+    ) -> Option<(NumberRange<i32>, Vec<CardInstanceInfo>)> {
+        let range = self.resolve_number_range(target, n);
+        let candidates: Vec<CardInstanceInfo> = self
+            .resolve_selector(target, selector)
+            .iter()
+            .map(|c| c.info())
+            .collect();
+        // 選ぶカードが無くて答えようがない質問はしない
+        if candidates.is_empty() && !range.contains(0) {
+            return None;
+        }
+        let range = range.limit_to(candidates.len() as i32);
+        self.awaiting = Some(Awaiting::Cards(
+            range.clone(),
+            candidates.iter().map(|c| c.instance_id).collect(),
+        ));
+        Some((range, candidates))
+    }
+
+    // 獲得の候補。山からは一番上のカード、廃棄置き場からは同名のカードを1枚ずつ
+    fn gain_candidates(
+        &self,
+        target: PlayerId,
+        source: &GainSource,
+        selector: &CardNameSelector,
+    ) -> Vec<&'_ CardInstance<'a>> {
+        let ids: Vec<CardInstanceId> = match source {
+            GainSource::Supply => self.supply.tops(true),
+            GainSource::NonSupply => self.supply.tops(false),
+            GainSource::Trash => self.trash.clone(),
+        };
+        let mut candidates: Vec<&CardInstance<'a>> = vec![];
+        for card in self.get_card_instances(&ids) {
+            if self.resolve_instance_name(target, selector, card)
+                && !candidates.iter().any(|c| c.card.name == card.card.name)
+            {
+                candidates.push(card);
+            }
+        }
+        candidates
     }
 
     fn exec_effect_one(&mut self, frame: EffectStackFrame, effect: CardEffect) -> EffectStepResult {
@@ -260,6 +1588,7 @@ impl<'a> Game<'a> {
                 let mut newframe = frame;
                 newframe.effect_queue = VecDeque::from(vec![*effect]);
                 self.stack.push(newframe);
+                self.awaiting = Some(Awaiting::YesNo);
                 return AskOptional(target, prompt);
             }
             FocusAll(selector, effect) => {
@@ -280,15 +1609,11 @@ impl<'a> Game<'a> {
                 newframe.effect_queue = VecDeque::from(vec![*effect]);
                 newframe.focus = vec![];
                 self.stack.push(newframe);
-                return AskCard(
-                    target,
-                    prompt,
-                    self.resolve_number_range(target, &n),
-                    self.resolve_selector(target, &selector)
-                        .iter()
-                        .map(|c| c.info())
-                        .collect(),
-                );
+                let Some((range, candidates)) = self.card_question(target, &n, &selector) else {
+                    self.pop_frame();
+                    return SkipContinue;
+                };
+                return AskCard(target, prompt, range, candidates);
             }
             TrashSelect(n, selector, effect) => {
                 let target = frame.target;
@@ -302,14 +1627,11 @@ impl<'a> Game<'a> {
                 ]);
                 newframe.focus = vec![];
                 self.stack.push(newframe);
-                return AskTrash(
-                    target,
-                    self.resolve_number_range(target, &n),
-                    self.resolve_selector(target, &selector)
-                        .iter()
-                        .map(|c| c.info())
-                        .collect(),
-                );
+                let Some((range, candidates)) = self.card_question(target, &n, &selector) else {
+                    self.pop_frame();
+                    return SkipContinue;
+                };
+                return AskTrash(target, range, candidates);
             }
             DiscardSelect(n, selector, effect) => {
                 let target = frame.target;
@@ -323,26 +1645,542 @@ impl<'a> Game<'a> {
                 ]);
                 newframe.focus = vec![];
                 self.stack.push(newframe);
-                return AskDiscard(
-                    target,
-                    self.resolve_number_range(target, &n),
-                    self.resolve_selector(target, &selector)
-                        .iter()
-                        .map(|c| c.info())
-                        .collect(),
-                );
+                let Some((range, candidates)) = self.card_question(target, &n, &selector) else {
+                    self.pop_frame();
+                    return SkipContinue;
+                };
+                return AskDiscard(target, range, candidates);
+            }
+            Choose(prompt, n, options) => {
+                let target = frame.target;
+                let available: Vec<(usize, _)> = options
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, o)| {
+                        !prompt.hide_impossible()
+                            || o.cond.as_ref().is_none_or(|c| self.resolve_cond(target, c))
+                    })
+                    .collect();
+                if available.is_empty() {
+                    return SkipContinue;
+                }
+                let range = self
+                    .resolve_number_range(target, &n)
+                    .limit_to(available.len() as i32);
+                let infos = available
+                    .iter()
+                    .map(|(index, o)| ChoiceInfo {
+                        index: *index,
+                        label: o.label.clone(),
+                    })
+                    .collect();
+                let mut newframe = frame;
+                newframe.effect_queue = VecDeque::new();
+                self.stack.push(newframe);
+                self.awaiting = Some(Awaiting::Choice(range.clone(), available));
+                return AskChoice(target, prompt, range, infos);
+            }
+            PlusDraw(n) => {
+                let n = self.resolve_number(frame.target, &n);
+                self.draw(frame.target, n);
+                Continue
+            }
+            PlusAction(n) => {
+                self.actions += self.resolve_number(frame.target, &n);
+                Continue
+            }
+            PlusBuy(n) => {
+                self.buys += self.resolve_number(frame.target, &n);
+                Continue
+            }
+            PlusCoin(n) => {
+                self.coins += self.resolve_number(frame.target, &n);
+                Continue
+            }
+            PlusPotion(n) => {
+                self.potions += self.resolve_number(frame.target, &n);
+                Continue
+            }
+            MoveCard(selector, zone) => {
+                let target = frame.target;
+                let ids: Vec<CardInstanceId> = self
+                    .resolve_selector(target, &selector)
+                    .iter()
+                    .map(|c| c.id)
+                    .collect();
+                if ids.iter().all(|id| {
+                    self.defer_replaced(ActionKind::Move, target, *id, &zone)
+                        || self.move_card(*id, target, &zone)
+                }) {
+                    Continue
+                } else {
+                    SkipContinue
+                }
+            }
+            OrderCards(prompt, selector, zone) => {
+                let target = frame.target;
+                let cards: Vec<&CardInstance<'a>> = self.resolve_selector(target, &selector);
+                let ids: Vec<CardInstanceId> = cards.iter().map(|c| c.id).collect();
+                let infos: Vec<CardInstanceInfo> = cards.iter().map(|c| c.info()).collect();
+                let identical = infos.iter().all(|c| c.name == infos[0].name);
+                let mut newframe = frame;
+                newframe.effect_queue = VecDeque::from(vec![MoveCard(
+                    CardSelector {
+                        name: CardNameSelector::Any,
+                        zone: vec![Focused],
+                    },
+                    zone.clone(),
+                )]);
+                newframe.focus = ids.clone();
+                self.stack.push(newframe);
+                if identical {
+                    return Continue;
+                }
+                // 山札の上に置くときは、最後に置いたカードが一番上になる
+                self.awaiting = Some(Awaiting::Order(ids, matches!(zone, DeckTop | Deck)));
+                return AskOrder(target, prompt, infos);
+            }
+            If(cond, effect) => {
+                if self.resolve_cond(frame.target, &cond) {
+                    self.extend_frame(&[*effect]);
+                }
+                Continue
+            }
+            While(cond, effect) => {
+                if self.resolve_cond(frame.target, &cond) {
+                    self.extend_frame(&[(*effect).clone(), While(cond, effect)]);
+                }
+                Continue
+            }
+            Until(cond, effect) => {
+                if !self.resolve_cond(frame.target, &cond) {
+                    self.extend_frame(&[(*effect).clone(), Until(cond, effect)]);
+                }
+                Continue
+            }
+            NameCard(prompt, selector, effect) => {
+                let target = frame.target;
+                // ゲームに登場しないカードの名前も宣言できる
+                let mut names: Vec<String> = self
+                    .card_pool
+                    .iter()
+                    .copied()
+                    .chain(self.card_instances.values().map(|c| c.card))
+                    .filter(|card| self.resolve_name(target, &selector, card))
+                    .map(|card| card.name.clone())
+                    .collect();
+                names.sort();
+                names.dedup();
+                if names.is_empty() {
+                    return SkipContinue;
+                }
+                let mut newframe = frame;
+                newframe.effect_queue = VecDeque::from(vec![*effect]);
+                self.stack.push(newframe);
+                self.awaiting = Some(Awaiting::CardName(names.clone()));
+                return AskCardName(target, prompt, names);
+            }
+            ChoosePlayer(prompt, selector, effect) => {
+                let target = frame.target;
+                let candidates = self.resolve_players(frame.player, frame.target, &selector);
+                if candidates.is_empty() {
+                    return SkipContinue;
+                }
+                let mut newframe = frame;
+                newframe.effect_queue = VecDeque::from(vec![*effect]);
+                self.stack.push(newframe);
+                self.awaiting = Some(Awaiting::Player(candidates.clone()));
+                return AskPlayer(target, prompt, candidates);
+            }
+            ForEachPlayer(selector, effect) => {
+                // 最初のプレイヤーのフレームが一番上に来るよう、逆順に積む
+                for player in self
+                    .resolve_players(frame.player, frame.target, &selector)
+                    .into_iter()
+                    .rev()
+                {
+                    let mut newframe = frame.clone();
+                    newframe.target = player;
+                    newframe.effect_queue = VecDeque::from(vec![(*effect).clone()]);
+                    newframe.focus = vec![];
+                    self.stack.push(newframe);
+                }
+                Continue
+            }
+            AttackAllOpponents(effect) => {
+                let opponents =
+                    self.resolve_players(frame.player, frame.target, &PlayerSelector::Opponents);
+                // 相手ごとに、攻撃を受ける前にその相手のリアクション（堀、灯台など）を解決する
+                for player in opponents.into_iter().rev() {
+                    let attack = self.next_resolution;
+                    self.next_resolution += 1;
+                    self.attacks.insert(attack, false);
+                    let mut newframe = frame.clone();
+                    newframe.target = player;
+                    newframe.effect_queue = VecDeque::new();
+                    newframe.internal = Some(InternalStep::ResolveAttack(attack, effect.clone()));
+                    newframe.focus = vec![];
+                    self.stack.push(newframe);
+                    for (id, reaction) in self
+                        .active_rules(Some(player), &EffectTrigger::Attacked)
+                        .into_iter()
+                        .rev()
+                    {
+                        self.push_effect(player, reaction, Some(id));
+                        self.stack.last_mut().unwrap().replacing = Some(attack);
+                    }
+                }
+                Continue
+            }
+            AllOpponents(effect) => {
+                self.extend_frame(&[ForEachPlayer(PlayerSelector::Opponents, effect)]);
+                Continue
+            }
+            GiveCard(selector, receiver, zone) => {
+                let ids: Vec<CardInstanceId> = self
+                    .resolve_selector(frame.target, &selector)
+                    .iter()
+                    .map(|c| c.id)
+                    .collect();
+                let Some(receiver) = self
+                    .resolve_players(frame.player, frame.target, &receiver)
+                    .first()
+                    .copied()
+                else {
+                    return SkipContinue;
+                };
+                if ids.iter().all(|id| self.move_card(*id, receiver, &zone)) {
+                    Continue
+                } else {
+                    SkipContinue
+                }
+            }
+            TrashCard(selector) => {
+                let target = frame.target;
+                let ids: Vec<CardInstanceId> = self
+                    .resolve_selector(target, &selector)
+                    .iter()
+                    .map(|c| c.id)
+                    .collect();
+                let mut trashed = vec![];
+                for id in ids {
+                    if !self.defer_replaced(ActionKind::Trash, target, id, &Zone::Trash) {
+                        self.trash_card(id);
+                        trashed.push(id);
+                    }
+                }
+                self.fire_moved(target, &EffectTrigger::CardTrashed, &trashed);
+                Continue
+            }
+            DiscardCard(selector) => {
+                let target = frame.target;
+                let ids: Vec<CardInstanceId> = self
+                    .resolve_selector(target, &selector)
+                    .iter()
+                    .map(|c| c.id)
+                    .collect();
+                let mut discarded = vec![];
+                for id in ids {
+                    if self.defer_replaced(ActionKind::Discard, target, id, &Discard) {
+                        continue;
+                    }
+                    if self.move_card(id, target, &Discard) {
+                        discarded.push(id);
+                    }
+                }
+                self.fire_moved(target, &EffectTrigger::CardDiscarded, &discarded);
+                Continue
+            }
+            GainCardFrom(selector, zone) => {
+                let target = frame.target;
+                let ids: Vec<CardInstanceId> = self
+                    .resolve_selector(target, &selector)
+                    .iter()
+                    .map(|c| c.id)
+                    .collect();
+                if self.gain_cards(target, &ids, &zone) {
+                    Continue
+                } else {
+                    SkipContinue
+                }
+            }
+            GainCard(source, selector, zone) => {
+                let target = frame.target;
+                let candidates: Vec<CardInstanceInfo> = self
+                    .gain_candidates(target, &source, &selector)
+                    .iter()
+                    .map(|c| c.info())
+                    .collect();
+                if candidates.is_empty() {
+                    return Impossible(match (source, selector) {
+                        (GainSource::Trash, _) => ImpossibleReason::NothingToGain,
+                        (_, CardNameSelector::Name(name)) => ImpossibleReason::EmptyPile(name),
+                        _ => ImpossibleReason::NothingToGain,
+                    });
+                }
+                let mut newframe = frame;
+                newframe.effect_queue = VecDeque::from(vec![GainCardFrom(
+                    CardSelector {
+                        name: CardNameSelector::Any,
+                        zone: vec![Focused],
+                    },
+                    zone,
+                )]);
+                newframe.focus = vec![];
+                if let [card] = &candidates[..] {
+                    newframe.focus = vec![card.instance_id];
+                    self.stack.push(newframe);
+                    return Continue;
+                }
+                self.stack.push(newframe);
+                self.awaiting = Some(Awaiting::Cards(
+                    Exact(1),
+                    candidates.iter().map(|c| c.instance_id).collect(),
+                ));
+                return AskGain(target, candidates);
+            }
+            BindCards(name, selector) => {
+                let ids: Vec<CardInstanceId> = self
+                    .resolve_selector(frame.target, &selector)
+                    .iter()
+                    .map(|c| c.id)
+                    .collect();
+                self.bindings
+                    .entry(frame.resolution)
+                    .or_default()
+                    .insert(name, Binding::Cards(ids));
+                Continue
+            }
+            BindNumber(name, n) => {
+                let n = self.resolve_number(frame.target, &n);
+                self.bindings
+                    .entry(frame.resolution)
+                    .or_default()
+                    .insert(name, Binding::Number(n));
+                Continue
+            }
+            ModifyCost(scope, selector, n) => {
+                let amount = self.resolve_number(frame.target, &n);
+                self.cost_modifiers.push(CostModifier {
+                    scope,
+                    source: frame.cause,
+                    selector,
+                    amount,
+                });
+                Continue
+            }
+            MarkThisTurn(tag) => {
+                self.turn_marks.push((frame.cause, tag));
+                Continue
+            }
+            DelayThisTurn(trigger, effect) => {
+                self.delayed
+                    .push((frame.player, frame.cause, trigger, *effect));
+                Continue
+            }
+            ForEach(selector, effect) => {
+                let ids: Vec<CardInstanceId> = self
+                    .resolve_selector(frame.target, &selector)
+                    .iter()
+                    .map(|c| c.id)
+                    .collect();
+                // 最初のカードのフレームが一番上に来るよう、逆順に積む
+                for id in ids.into_iter().rev() {
+                    let mut newframe = frame.clone();
+                    newframe.effect_queue = VecDeque::from(vec![(*effect).clone()]);
+                    newframe.focus = vec![id];
+                    self.stack.push(newframe);
+                }
+                Continue
+            }
+            Repeat(n, effect) => {
+                let n = self.resolve_number(frame.target, &n).max(0) as usize;
+                self.extend_frame(&vec![*effect; n]);
+                Continue
             }
             RevealTop(n, effect) => {
                 let target = frame.target;
                 let topn = self.get_from_deck(target, self.resolve_number(target, &n));
+                for id in topn.iter() {
+                    self.place_card(*id, target, &Revealed);
+                }
                 let mut newframe = frame;
                 newframe.effect_queue = VecDeque::from(vec![*effect]);
-                newframe.focus = vec![];
+                newframe.focus = topn;
+                self.stack.push(newframe);
+                return Continue;
+            }
+            LookAtTop(n, effect) => {
+                let target = frame.target;
+                let topn = self.look_at_top(target, self.resolve_number(target, &n));
+                let mut newframe = frame;
+                newframe.effect_queue = VecDeque::from(vec![*effect]);
+                newframe.focus = topn;
                 self.stack.push(newframe);
                 return Continue;
             }
+            UseCard(selector) => {
+                let ids: Vec<CardInstanceId> = self
+                    .resolve_selector(frame.target, &selector)
+                    .iter()
+                    .map(|c| c.id)
+                    .collect();
+                if let [id] = ids[..] {
+                    return if self.play_card(frame.target, id) {
+                        Continue
+                    } else {
+                        SkipContinue
+                    };
+                }
+                // 1枚ずつ、前のカードの解決が終わってから次のカードを使う
+                for id in ids.into_iter().rev() {
+                    let mut newframe = frame.clone();
+                    newframe.effect_queue = VecDeque::from(vec![UseCard(CardSelector {
+                        name: CardNameSelector::Any,
+                        zone: vec![Focused],
+                    })]);
+                    newframe.focus = vec![id];
+                    self.stack.push(newframe);
+                }
+                Continue
+            }
+            ChoosePile(prompt, selector, effect) => {
+                let target = frame.target;
+                // 空の山も選べるが、条件があれば一番上のカードで判定するので当てはまらない
+                let candidates: Vec<(usize, String)> = self
+                    .supply
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, p)| p.in_supply)
+                    .filter(|(_, p)| match p.top() {
+                        Some(id) => {
+                            self.resolve_instance_name(target, &selector, &self.card_instances[&id])
+                        }
+                        None => matches!(selector, CardNameSelector::Any),
+                    })
+                    .map(|(i, p)| (i, p.name.clone()))
+                    .collect();
+                if candidates.is_empty() {
+                    return SkipContinue;
+                }
+                let mut newframe = frame;
+                newframe.effect_queue = VecDeque::from(vec![*effect]);
+                self.stack.push(newframe);
+                self.awaiting = Some(Awaiting::Pile(candidates.iter().map(|(i, _)| *i).collect()));
+                return AskPile(target, prompt, candidates);
+            }
+            AddPileTokens(selector, name, n) => {
+                let n = self.resolve_number(frame.target, &n);
+                for pile in self.resolve_piles(&selector) {
+                    let count = self.supply.piles[pile]
+                        .tokens
+                        .entry(name.clone())
+                        .or_default();
+                    *count = (*count + n).max(0);
+                }
+                Continue
+            }
+            AddTokens(name, n) => {
+                let n = self.resolve_number(frame.target, &n);
+                if let Some(playerdata) = self.get_player_mut(frame.target) {
+                    let count = playerdata.tokens.entry(name).or_default();
+                    *count = (*count + n).max(0);
+                }
+                Continue
+            }
+            ExtraTurn(selector, rules) => {
+                let Some(player) = self
+                    .resolve_players(frame.player, frame.target, &selector)
+                    .first()
+                    .copied()
+                else {
+                    return SkipContinue;
+                };
+                let controller = rules.contains(&TurnRule::Possessed).then_some(frame.player);
+                self.extra_turns.push(ExtraTurn {
+                    player,
+                    controller,
+                    rules,
+                });
+                Continue
+            }
+            PreventDefault => {
+                if let Some(id) = frame.replacing {
+                    if let Some(action) = self.pending.get_mut(&id) {
+                        action.cancelled = true;
+                    } else if let Some(prevented) = self.attacks.get_mut(&id) {
+                        *prevented = true;
+                    }
+                }
+                Continue
+            }
+            Redirect(zone) => {
+                if let Some(action) = frame.replacing.and_then(|id| self.pending.get_mut(&id)) {
+                    action.zone = zone;
+                    action.redirected = true;
+                }
+                Continue
+            }
             _ => SkipContinue,
         };
         result
     }
+
+    fn exec_internal(&mut self, frame: EffectStackFrame, step: InternalStep) -> EffectStepResult {
+        match step {
+            InternalStep::ResolveAttack(attack, effect) => {
+                // リアクションでPreventDefaultが解決されていたら、この相手は攻撃を受けない
+                if self.attacks.remove(&attack) == Some(false) {
+                    self.extend_frame(&[*effect]);
+                }
+                Continue
+            }
+            InternalStep::ApplyReplacements(id) => {
+                let Some(action) = self.pending.get(&id).cloned() else {
+                    return Continue;
+                };
+                if action.cancelled || action.replacements.is_empty() {
+                    self.pending.remove(&id);
+                    self.finish_action(action);
+                    return Continue;
+                }
+                let mut sources: Vec<CardInstanceId> = vec![];
+                for (source, _) in action.replacements.iter() {
+                    if !sources.contains(source) {
+                        sources.push(*source);
+                    }
+                }
+                // 適用し終えたら、またこのフレームに戻ってくる
+                self.stack.last_mut().unwrap().internal = Some(InternalStep::ApplyReplacements(id));
+                let source = match (&frame.focus[..], &sources[..]) {
+                    ([chosen], _) if sources.contains(chosen) => *chosen,
+                    (_, [only]) => *only,
+                    _ => {
+                        // 影響を受けるプレイヤーが、どの置換効果から適用するかを選ぶ
+                        let infos = self
+                            .get_card_instances(&sources)
+                            .iter()
+                            .map(|c| c.info())
+                            .collect();
+                        self.stack.last_mut().unwrap().focus = vec![];
+                        self.awaiting = Some(Awaiting::Cards(Exact(1), sources));
+                        return AskReplacement(action.player, infos);
+                    }
+                };
+                self.stack.last_mut().unwrap().focus = vec![];
+                let pending = self.pending.get_mut(&id).unwrap();
+                let position = pending
+                    .replacements
+                    .iter()
+                    .position(|(s, _)| *s == source)
+                    .unwrap();
+                let (_, effect) = pending.replacements.remove(position);
+                self.push_effect(action.player, effect, Some(source));
+                let top = self.stack.last_mut().unwrap();
+                top.focus = vec![action.card];
+                top.replacing = Some(id);
+                Continue
+            }
+        }
+    }
 }
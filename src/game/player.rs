@@ -1,9 +1,11 @@
-use crate::game::card_instance::CardInstanceId;
+use crate::{core::locale::Locale, game::card_instance::CardInstanceId};
+use std::collections::HashMap;
 
 #[derive(Clone)]
 #[allow(dead_code)]
 pub struct PlayerData {
     pub name: String,
+    pub locale: Locale,
     pub deck: Vec<CardInstanceId>,
     pub hand: Vec<CardInstanceId>,
     pub play: Vec<CardInstanceId>,
@@ -11,17 +13,43 @@ pub struct PlayerData {
     pub aside: Vec<CardInstanceId>,
     pub revealed: Vec<CardInstanceId>,
     pub discard: Vec<CardInstanceId>,
+    pub mats: HashMap<String, Vec<CardInstanceId>>, // マットの名前ごと
+    pub tokens: HashMap<String, i32>,               // 勝利点トークン、財源、村人など
     pub id: PlayerId,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+impl PlayerData {
+    pub fn new(id: PlayerId, name: &str, locale: Locale) -> Self {
+        Self {
+            name: name.to_owned(),
+            locale,
+            deck: vec![],
+            hand: vec![],
+            play: vec![],
+            pending: vec![],
+            aside: vec![],
+            revealed: vec![],
+            discard: vec![],
+            mats: HashMap::new(),
+            tokens: HashMap::new(),
+            id,
+        }
+    }
+
+    pub fn token(&self, name: &str) -> i32 {
+        self.tokens.get(name).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 #[allow(dead_code)]
 pub struct PlayerId {
     id: usize,
 }
 
 impl PlayerId {
-    pub fn new(id: usize) -> PlayerId {
-        PlayerId { id }
+    pub const fn new(id: usize) -> Self {
+        Self { id }
     }
 }
@@ -0,0 +1,87 @@
+use crate::game::card_instance::CardInstanceId;
+use std::{collections::HashMap, ops::Index};
+
+// 山1つ。末尾が一番上
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct Pile {
+    pub name: String, // ランダマイザーのカード名。混合山なら山の名前（騎士、城など）
+    pub cards: Vec<CardInstanceId>,
+    pub in_supply: bool,              // 偽なら褒賞や略奪品のようなサプライ外の山
+    pub tokens: HashMap<String, i32>, // 山に置かれたトークン
+}
+
+#[allow(dead_code)]
+impl Pile {
+    pub fn top(&self) -> Option<CardInstanceId> {
+        self.cards.last().copied()
+    }
+
+    pub const fn len(&self) -> usize {
+        self.cards.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+}
+
+// サプライの山とサプライ外の山。番号は作った順で、山が空になっても変わらない
+#[derive(Clone, Default)]
+#[allow(dead_code)]
+pub struct Supply {
+    pub piles: Vec<Pile>,
+}
+
+#[allow(dead_code)]
+impl Supply {
+    pub const fn len(&self) -> usize {
+        self.piles.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.piles.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Pile> {
+        self.piles.iter()
+    }
+
+    pub fn get(&self, pile: usize) -> Option<&Pile> {
+        self.piles.get(pile)
+    }
+
+    /// その名前の山の番号
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.piles.iter().position(|p| p.name == name)
+    }
+
+    pub fn pile(&self, name: &str) -> Option<&Pile> {
+        self.piles.iter().find(|p| p.name == name)
+    }
+
+    /// サプライにある空の山の数。サプライ外の山は数えない
+    pub fn empty_piles(&self) -> usize {
+        self.piles
+            .iter()
+            .filter(|p| p.in_supply && p.is_empty())
+            .count()
+    }
+
+    /// サプライ（in_supplyが偽ならサプライ外）の各山の一番上のカード
+    pub fn tops(&self, in_supply: bool) -> Vec<CardInstanceId> {
+        self.piles
+            .iter()
+            .filter(|p| p.in_supply == in_supply)
+            .filter_map(|p| p.top())
+            .collect()
+    }
+}
+
+impl Index<usize> for Supply {
+    type Output = Pile;
+
+    fn index(&self, pile: usize) -> &Pile {
+        &self.piles[pile]
+    }
+}
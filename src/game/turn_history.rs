@@ -0,0 +1,22 @@
+use crate::{
+    core::history::HistoryKind,
+    game::{card_instance::CardInstanceId, player::PlayerId},
+};
+
+// 1ターン分の出来事。出来事ごとに、それをしたプレイヤーを記録する
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct TurnHistory {
+    pub turn_player: PlayerId,
+    pub events: Vec<(HistoryKind, PlayerId, CardInstanceId)>,
+}
+
+#[allow(dead_code)]
+impl TurnHistory {
+    pub const fn new(turn_player: PlayerId) -> Self {
+        Self {
+            turn_player,
+            events: vec![],
+        }
+    }
+}
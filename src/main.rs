@@ -1,6 +1,7 @@
 mod core;
 mod expansions;
 mod game;
+mod setup;
 fn main() {
     println!("Hello, world!");
 }
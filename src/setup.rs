@@ -0,0 +1,3 @@
+pub mod preset;
+pub mod randomizer;
+pub mod setup_code;
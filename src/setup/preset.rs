@@ -0,0 +1,247 @@
+use crate::{
+    expansions::registry::{ExpansionRegistry, RegistryError},
+    game::{game::Game, player::PlayerData},
+};
+
+// 公式の推奨王国セット
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct KingdomPreset {
+    pub expansion: String, // 拡張のID
+    pub name: String,
+    pub edition: u32,
+    pub cards: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum PresetIssue {
+    MissingCard(String),                             // 登録されていない
+    FirstEditionOnly(String),                        // 第1版にしか無いカードで、登録されていない
+    EditionMismatch { preset: u32, available: u32 }, // 遊べるが、推奨された版とは違う
+    WrongSize(usize),
+    Setup(RegistryError),
+}
+
+impl PresetIssue {
+    // 版違いは遊べるので警告どまり
+    pub const fn is_blocking(&self) -> bool {
+        !matches!(self, Self::EditionMismatch { .. })
+    }
+}
+
+#[allow(dead_code)]
+impl KingdomPreset {
+    pub fn new(expansion: &str, name: &str, edition: u32, cards: &[&str]) -> Self {
+        Self {
+            expansion: expansion.to_owned(),
+            name: name.to_owned(),
+            edition,
+            cards: cards.iter().map(|c| (*c).to_owned()).collect(),
+        }
+    }
+
+    /// 登録済みのカードでこのセットが遊べるかを調べる
+    pub fn check(&self, registry: &ExpansionRegistry) -> Vec<PresetIssue> {
+        let mut issues = vec![];
+        if self.cards.len() != 10 {
+            issues.push(PresetIssue::WrongSize(self.cards.len()));
+        }
+        for name in self.cards.iter() {
+            if registry.card(name).is_some() {
+                continue;
+            }
+            if registry.is_removed_card(&self.expansion, name) {
+                issues.push(PresetIssue::FirstEditionOnly(name.clone()));
+            } else {
+                issues.push(PresetIssue::MissingCard(name.clone()));
+            }
+        }
+        if let Some(available) = registry.edition(&self.expansion) {
+            if available != self.edition {
+                issues.push(PresetIssue::EditionMismatch {
+                    preset: self.edition,
+                    available,
+                });
+            }
+        }
+        issues
+    }
+
+    /// このセットでゲームを準備する。基本カードは "basic" 拡張から用意する
+    pub fn setup_game<'r>(
+        &self,
+        registry: &'r ExpansionRegistry,
+        players: Vec<PlayerData>,
+    ) -> Result<Game<'r>, Vec<PresetIssue>> {
+        let issues = self.check(registry);
+        if issues.iter().any(PresetIssue::is_blocking) {
+            return Err(issues);
+        }
+        let kingdom: Vec<&str> = self.cards.iter().map(String::as_str).collect();
+        registry
+            .setup_kingdom_game(&["basic", &self.expansion], &kingdom, players, None)
+            .map_err(|e| vec![PresetIssue::Setup(e)])
+    }
+}
+
+#[allow(dead_code)]
+pub struct PresetCatalog {
+    presets: Vec<KingdomPreset>,
+}
+
+#[allow(dead_code)]
+impl PresetCatalog {
+    pub fn get(&self, expansion: &str, name: &str) -> Option<&KingdomPreset> {
+        self.presets
+            .iter()
+            .find(|p| p.expansion == expansion && p.name == name)
+    }
+
+    pub fn of_expansion(&self, expansion: &str) -> Vec<&KingdomPreset> {
+        self.presets
+            .iter()
+            .filter(|p| p.expansion == expansion)
+            .collect()
+    }
+
+    pub fn add(&mut self, preset: KingdomPreset) {
+        self.presets.push(preset);
+    }
+
+    pub fn builtin() -> Self {
+        let base2 = |name, cards| KingdomPreset::new("base", name, 2, cards);
+        let base1 = |name, cards| KingdomPreset::new("base", name, 1, cards);
+        Self {
+            presets: vec![
+                base2(
+                    "First Game",
+                    &[
+                        "Cellar", "Market", "Merchant", "Militia", "Mine", "Moat", "Remodel",
+                        "Smithy", "Village", "Workshop",
+                    ],
+                ),
+                base2(
+                    "Size Distortion",
+                    &[
+                        "Artisan",
+                        "Bandit",
+                        "Bureaucrat",
+                        "Chapel",
+                        "Festival",
+                        "Gardens",
+                        "Sentry",
+                        "Throne Room",
+                        "Witch",
+                        "Workshop",
+                    ],
+                ),
+                base2(
+                    "Deck Top",
+                    &[
+                        "Artisan",
+                        "Bureaucrat",
+                        "Council Room",
+                        "Festival",
+                        "Harbinger",
+                        "Laboratory",
+                        "Moneylender",
+                        "Sentry",
+                        "Vassal",
+                        "Village",
+                    ],
+                ),
+                base2(
+                    "Sleight of Hand",
+                    &[
+                        "Cellar",
+                        "Council Room",
+                        "Festival",
+                        "Gardens",
+                        "Library",
+                        "Harbinger",
+                        "Militia",
+                        "Poacher",
+                        "Smithy",
+                        "Throne Room",
+                    ],
+                ),
+                base2(
+                    "Improvements",
+                    &[
+                        "Artisan",
+                        "Cellar",
+                        "Market",
+                        "Merchant",
+                        "Mine",
+                        "Moat",
+                        "Moneylender",
+                        "Poacher",
+                        "Remodel",
+                        "Witch",
+                    ],
+                ),
+                base2(
+                    "Silver & Gold",
+                    &[
+                        "Bandit",
+                        "Bureaucrat",
+                        "Chapel",
+                        "Harbinger",
+                        "Laboratory",
+                        "Merchant",
+                        "Mine",
+                        "Moneylender",
+                        "Throne Room",
+                        "Vassal",
+                    ],
+                ),
+                base1(
+                    "Big Money (1st)",
+                    &[
+                        "Adventurer",
+                        "Bureaucrat",
+                        "Chancellor",
+                        "Chapel",
+                        "Feast",
+                        "Laboratory",
+                        "Market",
+                        "Mine",
+                        "Moneylender",
+                        "Throne Room",
+                    ],
+                ),
+                base1(
+                    "Interaction (1st)",
+                    &[
+                        "Bureaucrat",
+                        "Chancellor",
+                        "Council Room",
+                        "Festival",
+                        "Library",
+                        "Militia",
+                        "Moat",
+                        "Spy",
+                        "Thief",
+                        "Village",
+                    ],
+                ),
+                base1(
+                    "Village Square (1st)",
+                    &[
+                        "Bureaucrat",
+                        "Cellar",
+                        "Festival",
+                        "Library",
+                        "Market",
+                        "Remodel",
+                        "Smithy",
+                        "Throne Room",
+                        "Village",
+                        "Woodcutter",
+                    ],
+                ),
+            ],
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use std::fmt;
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::{
+    core::{
+        card::{Card, CardType},
+        number::{Number, NumberRange},
+    },
+    expansions::registry::ExpansionRegistry,
+};
+
+// 乱数の引き直し回数。これで満たせなければ満たせなかった要件を返す
+const ATTEMPTS: usize = 200;
+
+// 王国が満たすべき要件
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Requirement {
+    TypeCount(CardType, NumberRange<i32>),
+    ExpansionLimit(String, usize), // 拡張ID、最大枚数
+    CostCovered(NumberRange<i32>), // このコスト帯のカードが1枚以上ある
+    PlusBuy,
+    Trashing,
+}
+
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct KingdomConstraints {
+    pub size: usize,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub expansion_limits: Vec<(String, usize)>,
+    pub type_counts: Vec<(CardType, NumberRange<i32>)>,
+    pub cost_spread: Vec<NumberRange<i32>>,
+    pub require_buy: bool,
+    pub require_trashing: bool,
+}
+
+impl Default for KingdomConstraints {
+    fn default() -> Self {
+        Self {
+            size: 10,
+            include: vec![],
+            exclude: vec![],
+            expansion_limits: vec![],
+            type_counts: vec![],
+            cost_spread: vec![],
+            require_buy: false,
+            require_trashing: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum RandomizerError {
+    UnknownCard(String), // 選んだ拡張の王国カードに無い
+    RemovedCard(String), // 選んだ拡張の前の版にしか無い
+    IncludedAndExcluded(String),
+    TooManyIncluded { included: usize, size: usize },
+    NotEnoughCards { needed: usize, available: usize },
+    NoCandidate(Requirement),        // どの候補カードでも満たせない
+    Unsatisfiable(Vec<Requirement>), // 引き直しを尽くしても満たせなかった要件
+}
+
+impl Requirement {
+    const fn has_upper_bound(&self) -> bool {
+        match self {
+            Self::TypeCount(_, range) => !within_upper(range, i32::MAX),
+            Self::ExpansionLimit(..) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TypeCount(t, range) => write!(f, "{t:?}の枚数が{range:?}"),
+            Self::ExpansionLimit(id, n) => write!(f, "{id}のカードが{n}枚以下"),
+            Self::CostCovered(range) => write!(f, "コスト{range:?}のカードを含む"),
+            Self::PlusBuy => write!(f, "+購入を持つカードを含む"),
+            Self::Trashing => write!(f, "廃棄手段を持つカードを含む"),
+        }
+    }
+}
+
+impl fmt::Display for RandomizerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCard(name) => write!(f, "{name}は選んだ拡張の王国カードにありません"),
+            Self::RemovedCard(name) => write!(f, "{name}は選んだ拡張の前の版にしかありません"),
+            Self::IncludedAndExcluded(name) => {
+                write!(f, "{name}が採用と除外の両方に指定されています")
+            }
+            Self::TooManyIncluded { included, size } => {
+                write!(
+                    f,
+                    "採用指定が{included}枚あり、王国の{size}枚を超えています"
+                )
+            }
+            Self::NotEnoughCards { needed, available } => {
+                write!(
+                    f,
+                    "王国に{needed}枚必要ですが、候補が{available}枚しかありません"
+                )
+            }
+            Self::NoCandidate(req) => write!(f, "「{req}」を満たす候補カードがありません"),
+            Self::Unsatisfiable(reqs) => {
+                let reqs: Vec<String> = reqs.iter().map(|r| format!("「{r}」")).collect();
+                write!(
+                    f,
+                    "{}を同時に満たす王国が見つかりませんでした",
+                    reqs.join("")
+                )
+            }
+        }
+    }
+}
+
+// ランダマイザーはゲーム外で動くので、定数のコストだけを見る
+const fn printed_cost(card: &Card) -> Option<i32> {
+    match card.cost.coin {
+        Number::Constant(n) => Some(n),
+        _ => None,
+    }
+}
+
+fn has_buy(card: &Card) -> bool {
+    card.any_rule(&|e| e.gives_buy())
+}
+
+fn has_trashing(card: &Card) -> bool {
+    card.any_rule(&|e| e.trashes_own_cards())
+}
+
+// 上限のある要件について、カードを1枚加えても上限を超えないか
+const fn within_upper(range: &NumberRange<i32>, n: i32) -> bool {
+    match range {
+        NumberRange::Exact(m) | NumberRange::UpTo(m) | NumberRange::Range(_, m) => n <= *m,
+        NumberRange::AtLeast(_) | NumberRange::AnyNumber => true,
+    }
+}
+
+struct Randomizer<'r> {
+    registry: &'r ExpansionRegistry,
+    constraints: &'r KingdomConstraints,
+}
+
+#[allow(dead_code)]
+impl<'r> Randomizer<'r> {
+    fn requirements(&self) -> Vec<Requirement> {
+        let c = self.constraints;
+        let mut reqs: Vec<Requirement> = c
+            .type_counts
+            .iter()
+            .map(|(t, range)| Requirement::TypeCount(t.clone(), range.clone()))
+            .collect();
+        reqs.extend(
+            c.expansion_limits
+                .iter()
+                .map(|(id, n)| Requirement::ExpansionLimit(id.clone(), *n)),
+        );
+        reqs.extend(c.cost_spread.iter().cloned().map(Requirement::CostCovered));
+        if c.require_buy {
+            reqs.push(Requirement::PlusBuy);
+        }
+        if c.require_trashing {
+            reqs.push(Requirement::Trashing);
+        }
+        reqs
+    }
+
+    fn count_type(kingdom: &[&Card], t: &CardType) -> i32 {
+        kingdom.iter().filter(|c| c.types.contains(t)).count() as i32
+    }
+
+    fn count_expansion(&self, kingdom: &[&Card], id: &str) -> usize {
+        kingdom
+            .iter()
+            .filter(|c| self.registry.origin(&c.name) == Some(id))
+            .count()
+    }
+
+    fn is_met(&self, req: &Requirement, kingdom: &[&Card]) -> bool {
+        match req {
+            Requirement::TypeCount(t, range) => range.contains(Self::count_type(kingdom, t)),
+            Requirement::ExpansionLimit(id, n) => self.count_expansion(kingdom, id) <= *n,
+            Requirement::CostCovered(range) => kingdom
+                .iter()
+                .any(|c| printed_cost(c).is_some_and(|cost| range.contains(cost))),
+            Requirement::PlusBuy => kingdom.iter().any(|c| has_buy(c)),
+            Requirement::Trashing => kingdom.iter().any(|c| has_trashing(c)),
+        }
+    }
+
+    /// このカードを入れることで要件が満たされる方向に進むか
+    fn helps(req: &Requirement, card: &Card) -> bool {
+        match req {
+            Requirement::TypeCount(t, _) => card.types.contains(t),
+            Requirement::ExpansionLimit(..) => false,
+            Requirement::CostCovered(range) => {
+                printed_cost(card).is_some_and(|c| range.contains(c))
+            }
+            Requirement::PlusBuy => has_buy(card),
+            Requirement::Trashing => has_trashing(card),
+        }
+    }
+
+    /// カードを加えても上限付きの要件を破らないか
+    fn fits(&self, kingdom: &[&Card], card: &Card) -> bool {
+        let c = self.constraints;
+        c.type_counts.iter().all(|(t, range)| {
+            !card.types.contains(t) || within_upper(range, Self::count_type(kingdom, t) + 1)
+        }) && c.expansion_limits.iter().all(|(id, n)| {
+            self.registry.origin(&card.name) != Some(id.as_str())
+                || self.count_expansion(kingdom, id) < *n
+        })
+    }
+
+    fn attempt<'c>(
+        &self,
+        reqs: &[Requirement],
+        included: &[&'c Card],
+        shuffled: &[&'c Card],
+    ) -> Vec<&'c Card> {
+        let mut kingdom = included.to_vec();
+        // まず満たされていない要件を満たすカードを優先して入れ、残りを順に埋める
+        for req in reqs {
+            while !self.is_met(req, &kingdom) && kingdom.len() < self.constraints.size {
+                let Some(card) = shuffled.iter().find(|c| {
+                    Self::helps(req, c)
+                        && !kingdom.iter().any(|k| k.name == c.name)
+                        && self.fits(&kingdom, c)
+                }) else {
+                    break;
+                };
+                kingdom.push(card);
+            }
+        }
+        for card in shuffled {
+            if kingdom.len() >= self.constraints.size {
+                break;
+            }
+            if !kingdom.iter().any(|k| k.name == card.name) && self.fits(&kingdom, card) {
+                kingdom.push(card);
+            }
+        }
+        kingdom
+    }
+}
+
+/// 選んだ拡張から王国カードを制約付きで無作為に選ぶ。同じシードなら同じ結果になる
+#[allow(dead_code)]
+pub fn randomize_kingdom(
+    registry: &ExpansionRegistry,
+    expansions: &[&str],
+    constraints: &KingdomConstraints,
+    seed: u64,
+) -> Result<Vec<String>, RandomizerError> {
+    let randomizer = Randomizer {
+        registry,
+        constraints,
+    };
+    let candidates: Vec<&Card> = registry
+        .kingdom_cards(expansions)
+        .into_iter()
+        .filter(|c| !constraints.exclude.contains(&c.name))
+        .collect();
+
+    let mut included = vec![];
+    for name in constraints.include.iter() {
+        if constraints.exclude.contains(name) {
+            return Err(RandomizerError::IncludedAndExcluded(name.clone()));
+        }
+        let Some(card) = candidates.iter().find(|c| c.name == *name) else {
+            if expansions
+                .iter()
+                .any(|id| registry.is_removed_card(id, name))
+            {
+                return Err(RandomizerError::RemovedCard(name.clone()));
+            }
+            return Err(RandomizerError::UnknownCard(name.clone()));
+        };
+        included.push(*card);
+    }
+    if included.len() > constraints.size {
+        return Err(RandomizerError::TooManyIncluded {
+            included: included.len(),
+            size: constraints.size,
+        });
+    }
+    if candidates.len() < constraints.size {
+        return Err(RandomizerError::NotEnoughCards {
+            needed: constraints.size,
+            available: candidates.len(),
+        });
+    }
+
+    let reqs = randomizer.requirements();
+    for req in reqs.iter() {
+        // 候補を全部入れても満たせない下限は、引き直すまでもなく無理
+        let satisfiable = match req {
+            Requirement::ExpansionLimit(..) => true,
+            Requirement::TypeCount(t, range) => {
+                let available = Randomizer::count_type(&candidates, t);
+                (0..=available.min(constraints.size as i32)).any(|n| range.contains(n))
+            }
+            _ => candidates.iter().any(|c| Randomizer::helps(req, c)),
+        };
+        if !satisfiable {
+            return Err(RandomizerError::NoCandidate(req.clone()));
+        }
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut rest: Vec<&Card> = candidates
+        .iter()
+        .copied()
+        .filter(|c| !constraints.include.contains(&c.name))
+        .collect();
+    rest.sort_by(|a, b| a.name.cmp(&b.name)); // 登録順に依存せず、シードだけで結果が決まるように
+    let mut best_unmet: Option<Vec<Requirement>> = None;
+    for _ in 0..ATTEMPTS {
+        rest.shuffle(&mut rng);
+        let kingdom = randomizer.attempt(&reqs, &included, &rest);
+        let mut unmet: Vec<Requirement> = reqs
+            .iter()
+            .filter(|r| !randomizer.is_met(r, &kingdom))
+            .cloned()
+            .collect();
+        if kingdom.len() < constraints.size {
+            // 枚数が足りないのは上限付きの要件のせい
+            let limits: Vec<Requirement> = reqs
+                .iter()
+                .filter(|r| r.has_upper_bound() && !unmet.contains(r))
+                .cloned()
+                .collect();
+            unmet.extend(limits);
+        }
+        if unmet.is_empty() {
+            let mut names: Vec<String> = kingdom.iter().map(|c| c.name.clone()).collect();
+            names.sort();
+            return Ok(names);
+        }
+        if best_unmet.as_ref().is_none_or(|b| unmet.len() < b.len()) {
+            best_unmet = Some(unmet);
+        }
+    }
+    Err(RandomizerError::Unsatisfiable(
+        best_unmet.unwrap_or_default(),
+    ))
+}
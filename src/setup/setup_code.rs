@@ -0,0 +1,292 @@
+use std::fmt;
+
+use crate::{
+    core::{card::Card, locale::Locale},
+    expansions::{
+        basic_supply::victory_pile_size,
+        registry::{ExpansionRegistry, RegistryError},
+    },
+    game::{game::Game, player::PlayerData},
+};
+
+/* セットアップコード
+  DMN1:<人数>:<オプション>:<シード>:<王国>:<イベント>
+  - オプション：c = 植民地、s = 避難所。無ければ空
+  - シード：36進数。指定しなければ空
+  - 王国・イベント：カード名を英小文字と数字だけにしたものを . で区切る
+  例：DMN1:2:c:2n9c:cellar.market.merchant.militia.mine.moat.remodel.smithy.village.workshop:
+*/
+const CODE_PREFIX: &str = "DMN1";
+// 王国の枚数。災いカードを使う若い魔女はまだ無いので、常に10枚
+const KINGDOM_SIZE: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub struct GameSetup {
+    pub kingdom: Vec<String>,
+    pub events: Vec<String>,
+    pub colonies: bool,
+    pub shelters: bool,
+    pub players: usize,
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum SetupCodeError {
+    Malformed(String), // 壊れている部分
+    UnsupportedVersion(String),
+    InvalidPlayerCount(usize),
+    UnknownCard(String),
+    AmbiguousCard(String, Vec<String>), // どのカードとも取れる入力と、その候補
+    NotKingdomCard(String),
+    NotLandscape(String), // イベント欄にランドスケープ以外のカードがある
+    WrongKingdomSize(usize),
+    DuplicateCard(String),
+    UnsupportedOption(String), // コードとしては読めるが、このエンジンではまだ準備できない
+    Setup(RegistryError),
+}
+
+impl fmt::Display for SetupCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(part) => write!(f, "コードの形式が正しくありません: {part}"),
+            Self::UnsupportedVersion(v) => write!(f, "対応していないコードです: {v}"),
+            Self::InvalidPlayerCount(n) => write!(f, "プレイヤー数{n}人では遊べません"),
+            Self::UnknownCard(name) => write!(f, "{name}というカードはありません"),
+            Self::AmbiguousCard(name, candidates) => {
+                write!(f, "{name}は{}のどれか分かりません", candidates.join("、"))
+            }
+            Self::NotKingdomCard(name) => write!(f, "{name}は王国カードではありません"),
+            Self::NotLandscape(name) => write!(f, "{name}はイベントやランドスケープではありません"),
+            Self::WrongKingdomSize(n) => {
+                write!(f, "王国カードが{n}枚あります（{KINGDOM_SIZE}枚必要です）")
+            }
+            Self::DuplicateCard(name) => write!(f, "{name}が重複しています"),
+            Self::UnsupportedOption(option) => write!(f, "{option}はまだ使えません"),
+            Self::Setup(e) => write!(f, "ゲームを準備できません: {e:?}"),
+        }
+    }
+}
+
+// カード名を比較用に正規化する（英小文字と数字だけにする）
+fn slug(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+// 入力を表示名と比較するための正規化。日本語名も比べられるよう、ASCII以外の文字も残す
+fn loose_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn to_base36(mut n: u64) -> String {
+    let digits = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    let mut s = vec![];
+    loop {
+        s.push(digits[(n % 36) as usize]);
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    s.reverse();
+    String::from_utf8(s).unwrap()
+}
+
+/// 入力されたカード名（英語名・各ロケールの表示名）を登録名に解決する
+fn resolve_card_name(registry: &ExpansionRegistry, text: &str) -> Result<String, SetupCodeError> {
+    let key = loose_key(text);
+    if key.is_empty() {
+        return Err(SetupCodeError::Malformed(text.to_owned()));
+    }
+    let mut matches: Vec<String> = registry
+        .expansion_ids()
+        .into_iter()
+        .flat_map(|id| registry.cards_of(id))
+        .filter(|card| {
+            slug(&card.name) == key
+                || [Locale::En, Locale::Ja].iter().any(|l| {
+                    registry
+                        .card_display_name(&card.name, *l)
+                        .is_some_and(|n| loose_key(n) == key)
+                })
+        })
+        .map(|card| card.name.clone())
+        .collect();
+    matches.sort();
+    matches.dedup();
+    match matches.len() {
+        0 => Err(SetupCodeError::UnknownCard(text.trim().to_owned())),
+        1 => Ok(matches.pop().unwrap()),
+        _ => Err(SetupCodeError::AmbiguousCard(
+            text.trim().to_owned(),
+            matches,
+        )),
+    }
+}
+
+fn resolve_kingdom(
+    registry: &ExpansionRegistry,
+    names: &[&str],
+) -> Result<Vec<String>, SetupCodeError> {
+    let kingdom_cards = registry.kingdom_cards(&registry.expansion_ids());
+    let mut kingdom: Vec<String> = vec![];
+    for text in names {
+        let name = resolve_card_name(registry, text)?;
+        if !kingdom_cards.iter().any(|c| c.name == name) {
+            return Err(SetupCodeError::NotKingdomCard(name));
+        }
+        if kingdom.contains(&name) {
+            return Err(SetupCodeError::DuplicateCard(name));
+        }
+        kingdom.push(name);
+    }
+    Ok(kingdom)
+}
+
+/// 他のツールから貼り付けた「Cellar, Market, Throne Room, ...」形式の王国を読む
+#[allow(dead_code)]
+pub fn import_kingdom_list(
+    registry: &ExpansionRegistry,
+    text: &str,
+) -> Result<Vec<String>, SetupCodeError> {
+    let names: Vec<&str> = text
+        .split([',', '、', '\n'])
+        .filter(|s| !s.trim().is_empty())
+        .collect();
+    resolve_kingdom(registry, &names)
+}
+
+#[allow(dead_code)]
+impl GameSetup {
+    pub fn encode(&self) -> String {
+        let options: String = [(self.colonies, 'c'), (self.shelters, 's')]
+            .iter()
+            .filter(|(on, _)| *on)
+            .map(|(_, c)| *c)
+            .collect();
+        let cards = |names: &[String]| names.iter().map(|n| slug(n)).collect::<Vec<_>>().join(".");
+        format!(
+            "{CODE_PREFIX}:{}:{}:{}:{}:{}",
+            self.players,
+            options,
+            self.seed.map(to_base36).unwrap_or_default(),
+            cards(&self.kingdom),
+            cards(&self.events),
+        )
+    }
+
+    /// コードを読み、登録済みのカードと照合する
+    pub fn decode(registry: &ExpansionRegistry, code: &str) -> Result<Self, SetupCodeError> {
+        let fields: Vec<&str> = code.trim().split(':').collect();
+        if fields[0] != CODE_PREFIX {
+            return Err(SetupCodeError::UnsupportedVersion(fields[0].to_owned()));
+        }
+        let [_, players, options, seed, kingdom, events] = fields[..] else {
+            return Err(SetupCodeError::Malformed(code.to_owned()));
+        };
+
+        let players: usize = players
+            .parse()
+            .map_err(|_| SetupCodeError::Malformed(players.to_owned()))?;
+        if !(1..=6).contains(&players) {
+            return Err(SetupCodeError::InvalidPlayerCount(players));
+        }
+        if let Some(c) = options.chars().find(|c| !matches!(c, 'c' | 's')) {
+            return Err(SetupCodeError::Malformed(c.to_string()));
+        }
+        let seed = if seed.is_empty() {
+            None
+        } else {
+            Some(
+                u64::from_str_radix(seed, 36)
+                    .map_err(|_| SetupCodeError::Malformed(seed.to_owned()))?,
+            )
+        };
+        let split = |s: &'_ str| -> Vec<String> {
+            s.split('.')
+                .filter(|n| !n.is_empty())
+                .map(str::to_owned)
+                .collect()
+        };
+        let kingdom = split(kingdom);
+        let kingdom = resolve_kingdom(
+            registry,
+            &kingdom.iter().map(String::as_str).collect::<Vec<_>>(),
+        )?;
+        if kingdom.len() != KINGDOM_SIZE {
+            return Err(SetupCodeError::WrongKingdomSize(kingdom.len()));
+        }
+        let events = split(events)
+            .iter()
+            .map(|e| resolve_card_name(registry, e))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(name) = events
+            .iter()
+            .find(|name| !registry.card(name).is_some_and(Card::is_landscape))
+        {
+            return Err(SetupCodeError::NotLandscape(name.clone()));
+        }
+
+        Ok(Self {
+            kingdom,
+            events,
+            colonies: options.contains('c'),
+            shelters: options.contains('s'),
+            players,
+            seed,
+        })
+    }
+
+    /// このセットアップでゲームを準備する。拡張は王国カードの出身から決める
+    pub fn setup_game<'r>(
+        &self,
+        registry: &'r ExpansionRegistry,
+        players: Vec<PlayerData>,
+    ) -> Result<Game<'r>, SetupCodeError> {
+        if players.len() != self.players {
+            return Err(SetupCodeError::InvalidPlayerCount(players.len()));
+        }
+        let mut expansions = vec!["basic"];
+        for name in self.kingdom.iter() {
+            let origin = registry
+                .origin(name)
+                .ok_or_else(|| SetupCodeError::UnknownCard(name.clone()))?;
+            if !expansions.contains(&origin) {
+                expansions.push(origin);
+            }
+        }
+        // 避難所とイベントはまだエンジンが扱えない
+        if self.shelters {
+            return Err(SetupCodeError::UnsupportedOption("shelters".to_owned()));
+        }
+        if !self.events.is_empty() {
+            return Err(SetupCodeError::UnsupportedOption("events".to_owned()));
+        }
+        let colony_piles = if self.colonies {
+            let (Some(platinum), Some(colony)) =
+                (registry.card("Platinum"), registry.card("Colony"))
+            else {
+                return Err(SetupCodeError::UnsupportedOption("colonies".to_owned()));
+            };
+            vec![(platinum, 12), (colony, victory_pile_size(self.players))]
+        } else {
+            vec![]
+        };
+
+        let kingdom: Vec<&str> = self.kingdom.iter().map(String::as_str).collect();
+        let mut game = registry
+            .setup_kingdom_game(&expansions, &kingdom, players, self.seed)
+            .map_err(SetupCodeError::Setup)?;
+        for (card, n) in colony_piles {
+            game.add_supply_pile(card, n);
+        }
+        Ok(game)
+    }
+}
@@ -1,52 +1,46 @@
-use std::collections::HashMap;
-
 use crate::{
-    core::{card::Card, zone::Zone::*},
-    expansions::{base::*, basic_supply::*},
+    core::{
+        card::{Card, CardType},
+        cost::CardCost,
+        effect::{CardEffect, EffectTrigger},
+        locale::{Locale, LocaleCatalog},
+        number::Number::Constant,
+        zone::Zone::*,
+    },
+    expansions::{
+        base::*,
+        basic_supply::*,
+        registry::{ConflictPolicy, ExpansionRegistry},
+    },
     game::{
-        card_instance::{CardAddress::*, CardInstance, CardInstanceId},
         game::Game,
         player::{PlayerData, PlayerId},
     },
 };
 
-pub fn setup<'a>() -> Game<'a> {
-    let p0 = PlayerData {
-        id: PlayerId::new(0),
-        name: "Alice".to_owned(),
-        deck: vec![],
-        hand: vec![],
-        play: vec![],
-        pending: vec![],
-        aside: vec![],
-        revealed: vec![],
-        discard: vec![],
-    };
-
-    let p1 = PlayerData {
-        id: PlayerId::new(1),
-        name: "Bob".to_owned(),
-        deck: vec![],
-        hand: vec![],
-        play: vec![],
-        pending: vec![],
-        aside: vec![],
-        revealed: vec![],
-        discard: vec![],
-    };
+// 日本語のAliceと英語のBob
+pub fn players() -> Vec<PlayerData> {
+    vec![
+        PlayerData::new(PlayerId::new(0), "Alice", Locale::Ja),
+        PlayerData::new(PlayerId::new(1), "Bob", Locale::En),
+    ]
+}
 
-    Game {
-        players: vec![p0, p1],
-        supply: vec![],
-        trash: vec![],
-        turn: 0,
-        stack: vec![],
-    }
+pub fn setup<'a>() -> Game<'a> {
+    let mut catalog = LocaleCatalog::core();
+    catalog.extend(basic_supply_catalog());
+    catalog.extend(base_set_catalog());
+    Game::new(players(), catalog)
 }
 
-fn supply() -> HashMap<String, Card> {
-    let mut supply = basic_supply();
-    supply.extend(base_set());
+fn supply() -> ExpansionRegistry {
+    let mut supply = ExpansionRegistry::new();
+    supply
+        .register(basic_expansion(), ConflictPolicy::Reject)
+        .unwrap();
+    supply
+        .register(base_expansion(), ConflictPolicy::Reject)
+        .unwrap();
     supply
 }
 
@@ -57,57 +51,44 @@ fn supply() -> HashMap<String, Card> {
 場札：地下貯蔵庫、密猟者、衛兵
 処理中：商人、玉座の間
  */
-fn setup2(supply: &HashMap<String, Card>) -> Game<'_> {
+fn setup2(supply: &ExpansionRegistry) -> Game<'_> {
     let mut game = setup();
-    let alice = &mut game.players[0];
-    let mut cid = 0;
+    let alice = game.players[0].id;
     let hand = ["Copper", "Silver", "Gold", "Moat", "Bandit"];
     let deck = ["Estate", "Militia", "Bureaucrat", "Gardens", "Market"];
     let discard = ["Chapel", "Harbinger", "Witch", "Artisan", "Mine"];
     let play = ["Cellar", "Poacher", "Sentry"];
     let pending = ["Merchant", "Throne Room"];
     for card in hand.iter() {
-        alice.hand.push(CardInstance {
-            id: CardInstanceId::new(cid),
-            card: &supply[*card],
-            address: PlayerOwned(alice.id, Hand),
-        });
-        cid += 1;
+        game.put_card(alice, Hand, &supply[card]);
     }
     for card in deck.iter() {
-        alice.deck.push(CardInstance {
-            id: CardInstanceId::new(cid),
-            card: &supply[*card],
-            address: PlayerOwned(alice.id, Deck),
-        });
-        cid += 1;
+        game.put_card(alice, Deck, &supply[card]);
     }
     for card in discard.iter() {
-        alice.discard.push(CardInstance {
-            id: CardInstanceId::new(cid),
-            card: &supply[*card],
-            address: PlayerOwned(alice.id, Discard),
-        });
-        cid += 1;
+        game.put_card(alice, Discard, &supply[card]);
     }
     for card in play.iter() {
-        alice.play.push(CardInstance {
-            id: CardInstanceId::new(cid),
-            card: &supply[*card],
-            address: PlayerOwned(alice.id, Play),
-        });
-        cid += 1;
+        game.put_card(alice, Play, &supply[card]);
     }
     for card in pending.iter() {
-        alice.pending.push(CardInstance {
-            id: CardInstanceId::new(cid),
-            card: &supply[*card],
-            address: PlayerOwned(alice.id, Pending),
-        });
-        cid += 1;
+        game.put_card(alice, Pending, &supply[card]);
     }
     game
 }
+
+// テスト用のカード。コストは$3、勝利点は0
+fn test_card(name: &str, types: Vec<CardType>, rules: Vec<(EffectTrigger, CardEffect)>) -> Card {
+    Card {
+        name: name.to_owned(),
+        localized_name: name.to_owned(),
+        cost: CardCost::coin(3),
+        vp: Constant(0),
+        rules,
+        types,
+    }
+}
+
 mod resolvers {
     mod cardname {
         use crate::{
@@ -118,28 +99,23 @@ mod resolvers {
                 zone::Zone::*,
             },
             expansions::{base::*, basic_supply::*},
-            game::card_instance::{CardAddress::*, CardInstance, CardInstanceId},
             tests::setup,
         };
         #[test]
         fn cardname_exact() {
             let mut game = setup();
             let copper = copper();
-            let alice = &mut game.players[0];
-            let hand = [&copper, &copper, &copper];
-            for (i, card) in hand.iter().enumerate() {
-                alice.hand.push(CardInstance {
-                    id: CardInstanceId::new(i),
-                    card,
-                    address: PlayerOwned(alice.id, Hand),
-                });
+            let silver = silver();
+            let alice = game.players[0].id;
+            let hand = [&copper, &copper, &silver];
+            for card in hand {
+                game.put_card(alice, Hand, card);
             }
             let selector = CardSelector {
                 name: CardNameSelector::Name("Copper".to_owned()),
                 zone: vec![Hand],
             };
-            let alice = &game.players[0];
-            let result = game.resolve_selector(alice.id, &selector);
+            let result = game.resolve_selector(alice, &selector);
             assert_eq!(result.len(), 2);
         }
 
@@ -149,21 +125,16 @@ mod resolvers {
             let copper = copper();
             let silver = silver();
             let gold = gold();
-            let alice = &mut game.players[0];
+            let alice = game.players[0].id;
             let hand = [&copper, &silver, &silver, &gold];
-            for (i, card) in hand.iter().enumerate() {
-                alice.hand.push(CardInstance {
-                    id: CardInstanceId::new(i),
-                    card,
-                    address: PlayerOwned(alice.id, Hand),
-                });
+            for card in hand {
+                game.put_card(alice, Hand, card);
             }
             let selector = CardSelector {
                 name: CardNameSelector::Cost(Box::new(Exact(Constant(3)))),
                 zone: vec![Hand],
             };
-            let alice = &game.players[0];
-            let result = game.resolve_selector(alice.id, &selector);
+            let result = game.resolve_selector(alice, &selector);
             assert_eq!(result.len(), 2);
         }
 
@@ -173,21 +144,16 @@ mod resolvers {
             let copper = copper();
             let silver = silver();
             let gold = gold();
-            let alice = &mut game.players[0];
+            let alice = game.players[0].id;
             let hand = [&copper, &silver, &silver, &gold];
-            for (i, card) in hand.iter().enumerate() {
-                alice.hand.push(CardInstance {
-                    id: CardInstanceId::new(i),
-                    card,
-                    address: PlayerOwned(alice.id, Hand),
-                });
+            for card in hand {
+                game.put_card(alice, Hand, card);
             }
             let selector = CardSelector {
                 name: CardNameSelector::Cost(Box::new(UpTo(Constant(3)))),
                 zone: vec![Hand],
             };
-            let alice = &game.players[0];
-            let result = game.resolve_selector(alice.id, &selector);
+            let result = game.resolve_selector(alice, &selector);
             assert_eq!(result.len(), 3);
         }
 
@@ -197,14 +163,10 @@ mod resolvers {
             let copper = copper();
             let silver = silver();
             let gold = gold();
-            let alice = &mut game.players[0];
+            let alice = game.players[0].id;
             let hand = [&copper, &silver, &gold];
-            for (i, card) in hand.iter().enumerate() {
-                alice.hand.push(CardInstance {
-                    id: CardInstanceId::new(i),
-                    card,
-                    address: PlayerOwned(alice.id, Hand),
-                });
+            for card in hand {
+                game.put_card(alice, Hand, card);
             }
             let selector = CardSelector {
                 name: CardNameSelector::NameOr(vec![
@@ -213,8 +175,7 @@ mod resolvers {
                 ]),
                 zone: vec![Hand],
             };
-            let alice = &game.players[0];
-            let result = game.resolve_selector(alice.id, &selector);
+            let result = game.resolve_selector(alice, &selector);
             assert_eq!(result.len(), 2);
         }
 
@@ -227,14 +188,10 @@ mod resolvers {
             let moat = moat();
             let bandit = bandit();
 
-            let alice = &mut game.players[0];
+            let alice = game.players[0].id;
             let hand = [&copper, &silver, &gold, &moat, &bandit];
-            for (i, card) in hand.iter().enumerate() {
-                alice.hand.push(CardInstance {
-                    id: CardInstanceId::new(i),
-                    card,
-                    address: PlayerOwned(alice.id, Hand),
-                });
+            for card in hand {
+                game.put_card(alice, Hand, card);
             }
             let selector_t = CardSelector {
                 name: CardNameSelector::HasType(Treasure),
@@ -248,10 +205,9 @@ mod resolvers {
                 name: CardNameSelector::HasType(Reaction),
                 zone: vec![Hand],
             };
-            let alice = &game.players[0];
-            let result_t = game.resolve_selector(alice.id, &selector_t);
-            let result_a = game.resolve_selector(alice.id, &selector_a);
-            let result_r = game.resolve_selector(alice.id, &selector_r);
+            let result_t = game.resolve_selector(alice, &selector_t);
+            let result_a = game.resolve_selector(alice, &selector_a);
+            let result_r = game.resolve_selector(alice, &selector_r);
             assert_eq!(result_t.len(), 3);
             assert_eq!(result_a.len(), 2);
             assert_eq!(result_r.len(), 1);
@@ -266,21 +222,16 @@ mod resolvers {
             let moat = moat();
             let bandit = bandit();
 
-            let alice = &mut game.players[0];
+            let alice = game.players[0].id;
             let hand = [&copper, &silver, &gold, &moat, &bandit];
-            for (i, card) in hand.iter().enumerate() {
-                alice.hand.push(CardInstance {
-                    id: CardInstanceId::new(i),
-                    card,
-                    address: PlayerOwned(alice.id, Hand),
-                });
+            for card in hand {
+                game.put_card(alice, Hand, card);
             }
             let selector = CardSelector {
                 name: CardNameSelector::Any,
                 zone: vec![Hand],
             };
-            let alice = &game.players[0];
-            let result = game.resolve_selector(alice.id, &selector);
+            let result = game.resolve_selector(alice, &selector);
             assert_eq!(result.len(), 5);
         }
     }
@@ -327,7 +278,6 @@ mod resolvers {
 mod base {
     use crate::{
         core::zone::Zone::*,
-        game::card_instance::{CardAddress::*, CardInstance, CardInstanceId},
         tests::{setup2, supply},
     };
 
@@ -339,30 +289,2510 @@ mod base {
         let vp = game.calculate_vp(alice.id);
         assert_eq!(vp, 3); // 屋敷(1VP)x1 + 庭園(20枚: 2VP)x1
 
-        let alice = &mut game.players[0];
-        for i in 20..28 {
-            alice.deck.push(CardInstance {
-                id: CardInstanceId::new(i),
-                card: &supply["Copper"],
-                address: PlayerOwned(alice.id, Deck),
-            });
+        let alice = game.players[0].id;
+        for _ in 20..28 {
+            game.put_card(alice, Deck, &supply["Copper"]);
         }
 
-        let alice = &game.players[0];
-        let vp = game.calculate_vp(alice.id);
+        let vp = game.calculate_vp(alice);
         assert_eq!(vp, 3); // 屋敷(1VP)x1 + 庭園(28枚: 2VP)x1
 
-        let alice = &mut game.players[0];
-        for i in 28..30 {
-            alice.deck.push(CardInstance {
-                id: CardInstanceId::new(i),
-                card: &supply["Copper"],
-                address: PlayerOwned(alice.id, Deck),
-            });
+        for _ in 28..30 {
+            game.put_card(alice, Deck, &supply["Copper"]);
         }
 
-        let alice = &game.players[0];
-        let vp = game.calculate_vp(alice.id);
+        let vp = game.calculate_vp(alice);
         assert_eq!(vp, 4); // 屋敷(1VP)x1 + 庭園(30枚: 3VP)x1
     }
 }
+
+mod locale {
+    use crate::{
+        core::{
+            ask_tag::AskCardTag,
+            card::CardType::*,
+            locale::{Locale, LocaleKey},
+            zone::Zone,
+        },
+        tests::{setup, supply},
+    };
+
+    #[test]
+    fn card_name_per_player() {
+        let supply = supply();
+        let game = setup();
+        let alice = game.players[0].id; // ja
+        let bob = game.players[1].id; // en
+        let vassal = &supply["Vassal"];
+        assert_eq!(game.localized_card_name(alice, vassal), "家臣");
+        assert_eq!(game.localized_card_name(bob, vassal), "Vassal");
+        assert_eq!(game.localized_type_name(bob, &Attack).unwrap(), "Attack");
+        assert_eq!(
+            game.localized_zone_name(alice, &Zone::Hand).unwrap(),
+            "手札"
+        );
+    }
+
+    #[test]
+    fn prompt_and_log() {
+        let game = setup();
+        let bob = game.players[1].id;
+        let tag = AskCardTag::new("cellar", "捨て札にするカードを選んでください");
+        assert_eq!(
+            tag.prompt(&game.catalog, Locale::En),
+            "Choose cards to discard"
+        );
+        let unknown = AskCardTag::new("unknown", "フォールバック");
+        assert_eq!(unknown.prompt(&game.catalog, Locale::En), "フォールバック");
+        assert_eq!(
+            game.localized_log(bob, "gain", &[("player", "Bob"), ("card", "Silver")])
+                .unwrap(),
+            "Bob gains Silver"
+        );
+    }
+
+    #[test]
+    fn base_catalogs_complete() {
+        let supply = supply();
+        let game = setup();
+        let mut keys: Vec<LocaleKey> = supply
+            .expansion_ids()
+            .into_iter()
+            .flat_map(|id| supply.cards_of(id))
+            .map(|card| LocaleKey::CardName(card.name.clone()))
+            .collect();
+        keys.extend(
+            [Action, Treasure, Victory, Reaction, Curse, Attack]
+                .into_iter()
+                .map(LocaleKey::CardType),
+        );
+        for locale in [Locale::Ja, Locale::En] {
+            assert!(game.catalog.missing(locale, &keys).is_empty());
+        }
+    }
+}
+
+mod registry {
+    use crate::{
+        core::{
+            effect::CardEffect::GainCard, locale::Locale, selector::CardNameSelector::Name,
+            zone::Zone,
+        },
+        expansions::{
+            base::*,
+            basic_supply::*,
+            registry::{ConflictPolicy, ExpansionRegistry, RegistryError},
+        },
+        game::player::{PlayerData, PlayerId},
+        tests::{players, supply},
+    };
+
+    #[test]
+    fn origin_and_setup() {
+        let supply = supply();
+        assert_eq!(supply.origin("Copper"), Some("basic"));
+        assert_eq!(supply.origin("Cellar"), Some("base"));
+        assert_eq!(supply.display_name("base", Locale::En), Some("Base Set"));
+
+        let game = supply
+            .setup_game(&["basic", "base"], players(), None)
+            .unwrap();
+        assert_eq!(game.supply.len(), 7);
+        assert_eq!(game.supply[0].len(), 46); // 銅貨60枚から初期デッキ分を除く
+        assert_eq!(game.players[1].deck.len(), 10);
+        assert_eq!(game.calculate_vp(game.players[0].id), 3);
+
+        // 初期デッキはシャッフルされ、同じシードなら同じ並びになる
+        let deck = |seed| {
+            let game = supply
+                .setup_game(&["basic", "base"], players(), Some(seed))
+                .unwrap();
+            game.resolve_zone(game.players[0].id, &Zone::Deck)
+                .iter()
+                .map(|c| c.card.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(deck(1), deck(1));
+        assert!((2..10).any(|seed| deck(seed) != deck(1)));
+    }
+
+    #[test]
+    fn name_conflict() {
+        let mut registry = ExpansionRegistry::new();
+        registry
+            .register(basic_expansion(), ConflictPolicy::Reject)
+            .unwrap();
+        let mut copy = base_expansion();
+        copy.id = "copy".to_owned();
+        copy.cards.push(copper());
+        assert_eq!(
+            registry.register(copy, ConflictPolicy::Reject),
+            Err(RegistryError::NameConflict {
+                name: "Copper".to_owned(),
+                existing: "basic".to_owned(),
+                incoming: "copy".to_owned(),
+            })
+        );
+        assert!(registry.card("Cellar").is_none());
+
+        let mut copy = base_expansion();
+        copy.cards.push(copper());
+        registry.register(copy, ConflictPolicy::Namespace).unwrap();
+        assert_eq!(registry.origin("Copper"), Some("basic"));
+        assert_eq!(registry.origin("base:Copper"), Some("base"));
+        assert_eq!(
+            registry.setup_game(&["unknown"], vec![], None).err(),
+            Some(RegistryError::UnknownExpansion("unknown".to_owned()))
+        );
+    }
+
+    #[test]
+    fn duplicates_and_renamed_references() {
+        let mut registry = ExpansionRegistry::new();
+        registry
+            .register(basic_expansion(), ConflictPolicy::Reject)
+            .unwrap();
+        let mut twice = base_expansion();
+        twice.cards.push(cellar());
+        assert_eq!(
+            registry.register(twice, ConflictPolicy::Namespace),
+            Err(RegistryError::DuplicateCard("Cellar".to_owned()))
+        );
+
+        // 名前空間を付けた銀貨を、同じ拡張の役人が参照し続ける
+        let mut copy = base_expansion();
+        copy.cards.push(silver());
+        registry.register(copy, ConflictPolicy::Namespace).unwrap();
+        let gains = |name: &str| {
+            let name = name.to_owned();
+            registry["Bureaucrat"].any_rule(&move |e| {
+                e.contains(&|e| matches!(e, GainCard(_, Name(n), _) if *n == name))
+            })
+        };
+        assert!(gains("base:Silver"));
+        assert!(!gains("Silver"));
+
+        // 準備処理の参照も付け替わり、もう一方の拡張のカードを使わない
+        let mut basic = basic_expansion();
+        basic.id = "basic2".to_owned();
+        registry.register(basic, ConflictPolicy::Namespace).unwrap();
+        let players = vec![PlayerData::new(PlayerId::new(0), "Alice", Locale::Ja)];
+        let game = registry.setup_game(&["basic2"], players, None).unwrap();
+        assert!(game.supply.pile("basic2:Copper").is_some());
+        assert!(game.supply.pile("Copper").is_none());
+        assert!(game
+            .resolve_zone(game.players[0].id, &Zone::Deck)
+            .iter()
+            .all(|c| c.card.name.starts_with("basic2:")));
+
+        assert_eq!(
+            registry
+                .setup_game(&["basic", "base", "basic"], vec![], None)
+                .err(),
+            Some(RegistryError::DuplicateExpansion("basic".to_owned()))
+        );
+    }
+}
+
+mod randomizer {
+    use crate::{
+        core::{card::CardType::*, number::NumberRange::*},
+        setup::randomizer::{randomize_kingdom, KingdomConstraints, RandomizerError, Requirement},
+        tests::supply,
+    };
+
+    #[test]
+    fn seeded() {
+        let supply = supply();
+        let constraints = KingdomConstraints::default();
+        let a = randomize_kingdom(&supply, &["basic", "base"], &constraints, 42).unwrap();
+        let b = randomize_kingdom(&supply, &["basic", "base"], &constraints, 42).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 10);
+        assert!(a.iter().all(|name| supply.origin(name) == Some("base")));
+        let mut dedup = a;
+        dedup.dedup();
+        assert_eq!(dedup.len(), 10);
+    }
+
+    #[test]
+    fn constrained() {
+        let supply = supply();
+        let constraints = KingdomConstraints {
+            include: vec!["Witch".to_owned()],
+            exclude: vec!["Chapel".to_owned(), "Market".to_owned()],
+            type_counts: vec![(Attack, UpTo(2))],
+            cost_spread: vec![UpTo(3), AtLeast(6)],
+            require_buy: true,
+            require_trashing: true,
+            ..Default::default()
+        };
+        for seed in 0..20 {
+            let kingdom = randomize_kingdom(&supply, &["base"], &constraints, seed).unwrap();
+            let cards: Vec<_> = kingdom.iter().map(|name| &supply[name.as_str()]).collect();
+            assert!(kingdom.contains(&"Witch".to_owned()));
+            assert!(!kingdom.contains(&"Chapel".to_owned()));
+            assert!(cards.iter().filter(|c| c.types.contains(&Attack)).count() <= 2);
+            assert!(cards.iter().any(|c| c.any_rule(&|e| e.gives_buy())));
+            assert!(cards.iter().any(|c| c.any_rule(&|e| e.trashes_own_cards())));
+            assert!(kingdom.contains(&"Artisan".to_owned())); // コスト6以上は職人だけ
+        }
+    }
+
+    #[test]
+    fn explanations() {
+        let supply = supply();
+        let run = |constraints: KingdomConstraints| {
+            randomize_kingdom(&supply, &["basic", "base"], &constraints, 0).unwrap_err()
+        };
+        assert_eq!(
+            run(KingdomConstraints {
+                include: vec!["Copper".to_owned()],
+                ..Default::default()
+            }),
+            RandomizerError::UnknownCard("Copper".to_owned())
+        );
+        assert_eq!(
+            run(KingdomConstraints {
+                include: vec!["Chancellor".to_owned()],
+                ..Default::default()
+            }),
+            RandomizerError::RemovedCard("Chancellor".to_owned())
+        );
+        assert_eq!(
+            run(KingdomConstraints {
+                include: vec!["Witch".to_owned()],
+                exclude: vec!["Witch".to_owned()],
+                ..Default::default()
+            }),
+            RandomizerError::IncludedAndExcluded("Witch".to_owned())
+        );
+        assert_eq!(
+            run(KingdomConstraints {
+                exclude: ["Market", "Festival", "Council Room"]
+                    .map(str::to_owned)
+                    .to_vec(),
+                require_buy: true,
+                ..Default::default()
+            }),
+            RandomizerError::NoCandidate(Requirement::PlusBuy)
+        );
+        let err = run(KingdomConstraints {
+            type_counts: vec![(Action, Exact(0))],
+            ..Default::default()
+        });
+        assert_eq!(
+            err,
+            RandomizerError::Unsatisfiable(vec![Requirement::TypeCount(Action, Exact(0))])
+        );
+        assert!(!err.to_string().is_empty());
+    }
+}
+
+mod preset {
+    use crate::{
+        core::zone::Zone,
+        setup::preset::{PresetCatalog, PresetIssue},
+        tests::{players, supply},
+    };
+
+    #[test]
+    fn second_edition_presets_playable() {
+        let supply = supply();
+        let presets = PresetCatalog::builtin();
+        for preset in presets.of_expansion("base") {
+            if preset.edition == 2 {
+                assert_eq!(preset.check(&supply), vec![], "{}", preset.name);
+            }
+        }
+        let game = presets
+            .get("base", "Size Distortion")
+            .unwrap()
+            .setup_game(&supply, players())
+            .unwrap();
+        assert_eq!(game.supply.len(), 17);
+        assert_eq!(game.supply.iter().filter(|p| p.len() == 8).count(), 4); // 屋敷・公領・属州・庭園
+
+        // 初期デッキはシャッフルされていて、毎回同じ手札で始まるわけではない
+        let deck = || {
+            let game = presets
+                .get("base", "Size Distortion")
+                .unwrap()
+                .setup_game(&supply, players())
+                .unwrap();
+            game.resolve_zone(game.players[0].id, &Zone::Deck)
+                .iter()
+                .map(|c| c.card.name.clone())
+                .collect::<Vec<_>>()
+        };
+        let first = deck();
+        assert!((0..5).any(|_| deck() != first));
+    }
+
+    #[test]
+    fn first_edition_flagged() {
+        let supply = supply();
+        let presets = PresetCatalog::builtin();
+        let big_money = presets.get("base", "Big Money (1st)").unwrap();
+        let issues = big_money.check(&supply);
+        assert_eq!(
+            issues,
+            vec![
+                PresetIssue::FirstEditionOnly("Adventurer".to_owned()),
+                PresetIssue::FirstEditionOnly("Chancellor".to_owned()),
+                PresetIssue::FirstEditionOnly("Feast".to_owned()),
+                PresetIssue::EditionMismatch {
+                    preset: 1,
+                    available: 2
+                },
+            ]
+        );
+        assert!(big_money.setup_game(&supply, players()).is_err());
+    }
+}
+
+mod setup_code {
+    use crate::{
+        core::{locale::Locale, zone::Zone},
+        game::player::{PlayerData, PlayerId},
+        setup::setup_code::{import_kingdom_list, GameSetup, SetupCodeError},
+        tests::{players, supply},
+    };
+
+    fn first_game() -> Vec<String> {
+        [
+            "Cellar",
+            "Market",
+            "Merchant",
+            "Militia",
+            "Mine",
+            "Moat",
+            "Remodel",
+            "Smithy",
+            "Throne Room",
+            "Workshop",
+        ]
+        .map(str::to_owned)
+        .to_vec()
+    }
+
+    #[test]
+    fn round_trip() {
+        let supply = supply();
+        let setup = GameSetup {
+            kingdom: first_game(),
+            events: vec![],
+            colonies: false,
+            shelters: false,
+            players: 2,
+            seed: Some(123456789),
+        };
+        let code = setup.encode();
+        assert!(code.starts_with("DMN1:2::21i3v9:cellar.market."));
+        assert_eq!(GameSetup::decode(&supply, &code), Ok(setup.clone()));
+
+        let game = setup.setup_game(&supply, players()).unwrap();
+        assert_eq!(game.supply.len(), 17);
+
+        // 同じコードなら初期デッキの並びも同じ
+        let decks = |game: &crate::game::game::Game| {
+            game.players
+                .iter()
+                .map(|p| {
+                    game.resolve_zone(p.id, &Zone::Deck)
+                        .iter()
+                        .map(|c| c.card.name.clone())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let again = setup.setup_game(&supply, players()).unwrap();
+        assert_eq!(decks(&game), decks(&again));
+        let other = GameSetup {
+            seed: Some(1),
+            ..setup.clone()
+        };
+        let other = other.setup_game(&supply, players()).unwrap();
+        assert_ne!(decks(&game), decks(&other));
+    }
+
+    #[test]
+    fn decode_errors() {
+        let supply = supply();
+        assert_eq!(
+            GameSetup::decode(&supply, "XYZ9:2::::"),
+            Err(SetupCodeError::UnsupportedVersion("XYZ9".to_owned()))
+        );
+        assert_eq!(
+            GameSetup::decode(&supply, "DMN1:2::"),
+            Err(SetupCodeError::Malformed("DMN1:2::".to_owned()))
+        );
+        assert_eq!(
+            GameSetup::decode(&supply, "DMN1:9::::"),
+            Err(SetupCodeError::InvalidPlayerCount(9))
+        );
+        assert_eq!(
+            GameSetup::decode(&supply, "DMN1:2:::cellar.woodcutter:"),
+            Err(SetupCodeError::UnknownCard("woodcutter".to_owned()))
+        );
+        assert_eq!(
+            GameSetup::decode(&supply, "DMN1:2:::cellar.copper:"),
+            Err(SetupCodeError::NotKingdomCard("Copper".to_owned()))
+        );
+
+        assert_eq!(
+            GameSetup::decode(&supply, "DMN1:1:::cellar:"),
+            Err(SetupCodeError::WrongKingdomSize(1))
+        );
+        let kingdom = first_game().join(".").replace(' ', "");
+        assert_eq!(
+            GameSetup::decode(&supply, &format!("DMN1:2:::{kingdom}:village")),
+            Err(SetupCodeError::NotLandscape("Village".to_owned()))
+        );
+
+        let colonies = GameSetup::decode(&supply, &format!("DMN1:1:c::{kingdom}:")).unwrap();
+        assert!(colonies.colonies);
+        let players = vec![PlayerData::new(PlayerId::new(0), "Alice", Locale::Ja)];
+        assert_eq!(
+            colonies.setup_game(&supply, players).err(),
+            Some(SetupCodeError::UnsupportedOption("colonies".to_owned()))
+        );
+    }
+
+    #[test]
+    fn import_list() {
+        let supply = supply();
+        assert_eq!(
+            import_kingdom_list(&supply, "Cellar, market, THRONE ROOM, Vassal,\n村"),
+            Ok(["Cellar", "Market", "Throne Room", "Vassal", "Village"]
+                .map(str::to_owned)
+                .to_vec())
+        );
+        assert_eq!(
+            import_kingdom_list(&supply, "Cellar, Smithy, cellar"),
+            Err(SetupCodeError::DuplicateCard("Cellar".to_owned()))
+        );
+    }
+}
+
+mod choose {
+    use crate::{
+        core::{
+            ask_tag::{AskChoiceTag, ChoiceLabel},
+            effect::{CardEffect::*, ChoiceOption, EffectCond::*},
+            number::{Number::*, NumberRange::*},
+        },
+        expansions::card_util::hand,
+        game::effect_stack::{Answer, EffectStepResult},
+        tests::{setup2, supply},
+    };
+
+    // 執事：+2カード、+2金、手札2枚廃棄から1つ
+    fn steward(hide_impossible: bool) -> crate::core::effect::CardEffect {
+        Choose(
+            AskChoiceTag::new("steward", "1つを選んでください", hide_impossible),
+            Exact(Constant(1)),
+            vec![
+                ChoiceOption {
+                    label: ChoiceLabel::new("steward.draw", "+2カード"),
+                    cond: None,
+                    effect: PlusDraw(Constant(2)),
+                },
+                ChoiceOption {
+                    label: ChoiceLabel::new("steward.coin", "+2金"),
+                    cond: None,
+                    effect: PlusCoin(Constant(2)),
+                },
+                ChoiceOption {
+                    label: ChoiceLabel::new("steward.trash", "手札2枚を廃棄"),
+                    cond: Some(Geq(CountCard(hand()), Constant(2))),
+                    effect: TrashSelect(Exact(Constant(2)), hand(), Box::new(Noop)),
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn choose_one() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.push_effect(alice, steward(true), None);
+        let EffectStepResult::AskChoice(_, _, range, options) = game.run() else {
+            panic!("選択肢を聞かれるはず");
+        };
+        assert_eq!(range, Exact(1));
+        assert_eq!(options.len(), 3);
+        assert!(game.answer(Answer::Choice(vec![0, 1])).is_err());
+        assert!(game.answer(Answer::Cards(vec![])).is_err());
+        assert!(game.answer(Answer::Choice(vec![0])).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[0].hand.len(), 7);
+    }
+
+    #[test]
+    fn hide_impossible() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.players[0].hand.clear();
+        game.push_effect(alice, steward(true), None);
+        let EffectStepResult::AskChoice(_, _, _, options) = game.run() else {
+            panic!("選択肢を聞かれるはず");
+        };
+        let indices: Vec<usize> = options.iter().map(|o| o.index).collect();
+        assert_eq!(indices, vec![0, 1]);
+        assert!(game.answer(Answer::Choice(vec![2])).is_err());
+
+        let mut game = setup2(&supply);
+        game.players[0].hand.clear();
+        game.push_effect(alice, steward(false), None);
+        let EffectStepResult::AskChoice(_, _, _, options) = game.run() else {
+            panic!("選択肢を聞かれるはず");
+        };
+        assert_eq!(options.len(), 3);
+    }
+
+    #[test]
+    fn choose_two_in_card_order() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        // 手先：異なる2つを選ぶ
+        let pawn = Choose(
+            AskChoiceTag::new("pawn", "異なる2つを選んでください", false),
+            Exact(Constant(2)),
+            [
+                ("pawn.draw", PlusDraw(Constant(1))),
+                ("pawn.action", PlusAction(Constant(1))),
+                ("pawn.buy", PlusBuy(Constant(1))),
+                ("pawn.coin", PlusCoin(Constant(1))),
+            ]
+            .map(|(tag, effect)| ChoiceOption {
+                label: ChoiceLabel::new(tag, tag),
+                cond: None,
+                effect,
+            })
+            .to_vec(),
+        );
+        game.push_effect(alice, pawn, None);
+        assert!(matches!(game.run(), EffectStepResult::AskChoice(..)));
+        assert!(game.answer(Answer::Choice(vec![3, 3])).is_err());
+        assert!(game.answer(Answer::Choice(vec![3, 1])).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!((game.actions, game.buys, game.coins), (2, 1, 1));
+    }
+
+    #[test]
+    fn fewer_candidates_than_required() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        // 手札が2枚しか無ければ、3枚捨てる指示は2枚捨てるだけでよい
+        game.players[0].hand.truncate(2);
+        let discard = DiscardSelect(Exact(Constant(3)), hand(), Box::new(Noop));
+        game.push_effect(alice, discard, None);
+        let EffectStepResult::AskDiscard(_, range, candidates) = game.run() else {
+            panic!("捨てるカードを聞かれるはず");
+        };
+        assert_eq!(range, Exact(2));
+        let ids = candidates.iter().map(|c| c.instance_id).collect();
+        assert!(game.answer(Answer::Cards(ids)).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert!(game.players[0].hand.is_empty());
+
+        // 実行できる選択肢が1つしか残らなければ、2つ選ぶ指示でも1つ選ぶ
+        let impossible = Some(Geq(CountCard(hand()), Constant(1)));
+        let choose = Choose(
+            AskChoiceTag::new("pawn", "異なる2つを選んでください", true),
+            Exact(Constant(2)),
+            vec![
+                ChoiceOption {
+                    label: ChoiceLabel::new("pawn.coin", "+1金"),
+                    cond: None,
+                    effect: PlusCoin(Constant(1)),
+                },
+                ChoiceOption {
+                    label: ChoiceLabel::new("trash", "手札1枚を廃棄"),
+                    cond: impossible,
+                    effect: TrashSelect(Exact(Constant(1)), hand(), Box::new(Noop)),
+                },
+            ],
+        );
+        game.push_effect(alice, choose, None);
+        let EffectStepResult::AskChoice(_, _, range, _) = game.run() else {
+            panic!("選択肢を聞かれるはず");
+        };
+        assert_eq!(range, Exact(1));
+        assert!(game.answer(Answer::Choice(vec![0])).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.coins, 1);
+    }
+}
+
+mod order {
+    use crate::{
+        core::{ask_tag::AskCardTag, effect::CardEffect::*, zone::Zone},
+        expansions::card_util::in_zone,
+        game::effect_stack::{Answer, EffectStepResult},
+        tests::{setup, setup2, supply},
+    };
+
+    #[test]
+    fn order_onto_deck() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let effect = OrderCards(
+            AskCardTag::new("sentry", "順番を選んでください"),
+            in_zone(Zone::Pending),
+            Zone::DeckTop,
+        );
+        game.push_effect(alice, effect, None);
+        let EffectStepResult::AskOrder(_, _, cards) = game.run() else {
+            panic!("順番を聞かれるはず");
+        };
+        assert_eq!(cards.len(), 2); // 商人、玉座の間
+        let merchant = cards[0].instance_id;
+        let throne_room = cards[1].instance_id;
+        assert!(game.answer(Answer::Order(vec![throne_room])).is_err());
+        assert!(game
+            .answer(Answer::Order(vec![merchant, merchant]))
+            .is_err());
+        assert!(game
+            .answer(Answer::Order(vec![throne_room, merchant]))
+            .is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+
+        let deck = &game.players[0].deck;
+        assert_eq!(deck.len(), 7);
+        assert!(deck[deck.len() - 1] == throne_room);
+        assert!(deck[deck.len() - 2] == merchant);
+        assert!(game.players[0].pending.is_empty());
+    }
+
+    #[test]
+    fn identical_cards_not_asked() {
+        let supply = supply();
+        let mut game = setup();
+        let alice = game.players[0].id;
+        game.put_card(alice, Zone::Pending, &supply["Copper"]);
+        game.put_card(alice, Zone::Pending, &supply["Copper"]);
+        let effect = OrderCards(
+            AskCardTag::new("sentry", "順番を選んでください"),
+            in_zone(Zone::Pending),
+            Zone::DeckTop,
+        );
+        game.push_effect(alice, effect, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[0].deck.len(), 2);
+    }
+}
+
+mod name_card {
+    use crate::{
+        core::{
+            ask_tag::AskCardTag,
+            card::CardType::*,
+            effect::{CardEffect::*, EffectCond::*},
+            locale::Locale,
+            number::Number::*,
+            selector::{CardNameSelector::*, CardSelector},
+            zone::Zone,
+        },
+        expansions::card_util::focused,
+        game::{
+            effect_stack::{Answer, EffectStepResult},
+            player::{PlayerData, PlayerId},
+        },
+        tests::{setup2, supply},
+    };
+
+    #[test]
+    fn named_card_selector() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        // 財宝の名前を宣言し、手札のそのカードを捨て札にする。宣言したのが財宝なら+1金
+        let effect = NameCard(
+            AskCardTag::new("name_treasure", "財宝の名前を宣言してください"),
+            HasType(Treasure),
+            Box::new(Sequence(vec![
+                FocusAll(
+                    CardSelector {
+                        name: Named,
+                        zone: vec![Zone::Hand],
+                    },
+                    Box::new(MoveCard(focused(), Zone::Discard)),
+                ),
+                If(NamedIs(HasType(Treasure)), Box::new(PlusCoin(Constant(1)))),
+            ])),
+        );
+        game.push_effect(alice, effect, None);
+        let EffectStepResult::AskCardName(_, _, names) = game.run() else {
+            panic!("カード名を聞かれるはず");
+        };
+        assert_eq!(names, vec!["Copper", "Gold", "Silver"]);
+        assert!(game.answer(Answer::CardName("Estate".to_owned())).is_err());
+        assert!(game.answer(Answer::CardName("Silver".to_owned())).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[0].hand.len(), 4);
+        assert_eq!(game.players[0].discard.len(), 6);
+        assert_eq!(game.coins, 1);
+    }
+
+    #[test]
+    fn name_card_not_in_game() {
+        let supply = supply();
+        let players = vec![PlayerData::new(PlayerId::new(0), "Alice", Locale::Ja)];
+        let mut game = supply
+            .setup_game(&["basic", "base"], players, None)
+            .unwrap();
+        let alice = game.players[0].id;
+        // 王国に無いアタックカードの名前も宣言できる
+        let effect = NameCard(
+            AskCardTag::new("name_attack", "アタックカードの名前を宣言してください"),
+            HasType(Attack),
+            Box::new(If(
+                NamedIs(HasType(Attack)),
+                Box::new(PlusCoin(Constant(1))),
+            )),
+        );
+        game.push_effect(alice, effect, None);
+        let EffectStepResult::AskCardName(_, _, names) = game.run() else {
+            panic!("カード名を聞かれるはず");
+        };
+        assert!(names.contains(&"Witch".to_owned()));
+        assert!(game.answer(Answer::CardName("Witch".to_owned())).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.coins, 1);
+    }
+}
+
+mod choose_player {
+    use crate::{
+        core::{
+            ask_tag::{AskCardTag, AskPlayerTag},
+            effect::CardEffect::*,
+            number::{Number::*, NumberRange::*},
+            selector::PlayerSelector,
+            zone::Zone,
+        },
+        expansions::card_util::{focused, hand, in_zone},
+        game::{
+            card_instance::CardAddress,
+            effect_stack::{Answer, EffectStepResult},
+        },
+        tests::{setup2, supply},
+    };
+
+    #[test]
+    fn chosen_player_is_target() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        game.put_card(bob, Zone::Deck, &supply["Copper"]);
+        let effect = ChoosePlayer(
+            AskPlayerTag::new(
+                "choose_drawer",
+                "カードを引かせるプレイヤーを選んでください",
+            ),
+            PlayerSelector::Opponents,
+            Box::new(PlusDraw(Constant(1))),
+        );
+        game.push_effect(alice, effect, None);
+        let EffectStepResult::AskPlayer(asked, _, candidates) = game.run() else {
+            panic!("プレイヤーを聞かれるはず");
+        };
+        assert_eq!(asked, alice);
+        assert_eq!(candidates, vec![bob]);
+        assert!(game.answer(Answer::Player(alice)).is_err());
+        assert!(game.answer(Answer::Player(bob)).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[0].hand.len(), 5);
+        assert_eq!(game.players[1].hand.len(), 1);
+    }
+
+    #[test]
+    fn pass_to_the_left() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        game.put_card(bob, Zone::Hand, &supply["Estate"]);
+        // 仮面舞踏会：全員が1枚選んでから、同時に左隣へ渡す
+        let effect = Sequence(vec![
+            ForEachPlayer(
+                PlayerSelector::AllPlayers,
+                Box::new(Select(
+                    AskCardTag::new("masquerade", "左隣に渡すカードを選んでください"),
+                    Exact(Constant(1)),
+                    hand(),
+                    Box::new(MoveCard(focused(), Zone::Aside)),
+                )),
+            ),
+            ForEachPlayer(
+                PlayerSelector::AllPlayers,
+                Box::new(GiveCard(
+                    in_zone(Zone::Aside),
+                    PlayerSelector::LeftOfTarget,
+                    Zone::Hand,
+                )),
+            ),
+        ]);
+        game.push_effect(alice, effect, None);
+
+        let EffectStepResult::AskCard(asked, _, _, cards) = game.run() else {
+            panic!("カードを聞かれるはず");
+        };
+        assert_eq!(asked, alice);
+        let gold = cards.iter().find(|c| c.name == "Gold").unwrap().instance_id;
+        assert!(game.answer(Answer::Cards(vec![gold])).is_ok());
+        let EffectStepResult::AskCard(asked, _, _, cards) = game.run() else {
+            panic!("カードを聞かれるはず");
+        };
+        assert_eq!(asked, bob);
+        let estate = cards[0].instance_id;
+        assert!(game.answer(Answer::Cards(vec![estate])).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+
+        assert!(game.players[1].hand.contains(&gold));
+        assert!(game.players[0].hand.contains(&estate));
+        assert!(game.players[0].aside.is_empty() && game.players[1].aside.is_empty());
+        let bob_hand = game.resolve_zone(bob, &Zone::Hand);
+        assert!(matches!(
+            bob_hand[0].address,
+            CardAddress::PlayerOwned(owner, Zone::Hand) if owner == bob
+        ));
+    }
+}
+
+mod gain {
+    use crate::{
+        core::{
+            card::CardType::Action,
+            effect::{CardEffect::*, GainSource},
+            number::{Number::*, NumberRange::*},
+            selector::{CardNameSelector::*, CardSelector},
+            zone::Zone,
+        },
+        expansions::{base::bureaucrat, card_util::hand},
+        game::{
+            card_instance::CardAddress,
+            effect_stack::{Answer, EffectStepResult, ImpossibleReason},
+        },
+        tests::{setup2, supply},
+    };
+
+    #[test]
+    fn gain_onto_deck() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.add_supply_pile(&supply["Silver"], 40);
+        game.push_effect(alice, bureaucrat().rules[0].1.clone(), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.supply[0].len(), 39);
+        let top = game.resolve_zone(alice, &Zone::DeckTop);
+        assert_eq!(top[0].card.name, "Silver");
+        assert!(matches!(
+            top[0].address,
+            CardAddress::PlayerOwned(_, Zone::Deck)
+        ));
+    }
+
+    #[test]
+    fn choose_from_supply() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        for card in ["Copper", "Silver", "Gold"] {
+            game.add_supply_pile(&supply[card], 10);
+        }
+        let effect = GainCard(
+            GainSource::Supply,
+            Cost(Box::new(UpTo(Constant(4)))),
+            Zone::Hand,
+        );
+        game.push_effect(alice, effect, None);
+        let EffectStepResult::AskGain(_, cards) = game.run() else {
+            panic!("獲得するカードを聞かれるはず");
+        };
+        assert_eq!(cards.len(), 2); // 銅貨、銀貨
+        let silver = cards[1].instance_id;
+        assert!(game.answer(Answer::Cards(vec![])).is_err());
+        assert!(game.answer(Answer::Cards(vec![silver])).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert!(game.players[0].hand.contains(&silver));
+        assert_eq!(game.supply[1].len(), 9);
+    }
+
+    #[test]
+    fn gain_from_trash() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        // 手札の山賊を廃棄してから、廃棄置き場のアクションカードを獲得する
+        let effect = Sequence(vec![
+            TrashCard(CardSelector {
+                name: Name("Bandit".to_owned()),
+                zone: vec![Zone::Hand],
+            }),
+            GainCard(GainSource::Trash, HasType(Action), Zone::Discard),
+        ]);
+        game.push_effect(alice, effect, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert!(game.trash.is_empty());
+        assert_eq!(game.resolve_zone(alice, &Zone::Discard).len(), 6);
+        assert_eq!(game.resolve_selector(alice, &hand()).len(), 4);
+    }
+
+    #[test]
+    fn empty_pile_is_impossible() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.add_supply_pile(&supply["Gold"], 0);
+        let effect = AtomicSequence(vec![
+            GainCard(GainSource::Supply, Name("Gold".to_owned()), Zone::Discard),
+            PlusCoin(Constant(1)),
+        ]);
+        game.push_effect(alice, effect, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(
+            game.impossible,
+            vec![ImpossibleReason::EmptyPile("Gold".to_owned())]
+        );
+        assert_eq!(game.coins, 0);
+    }
+}
+
+mod for_each {
+    use crate::{
+        core::{effect::CardEffect::*, number::Number::*, zone::Zone},
+        expansions::{
+            base::sentry,
+            card_util::{focused, hand},
+        },
+        game::effect_stack::{Answer, EffectStepResult},
+        tests::{setup, setup2, supply},
+    };
+
+    #[test]
+    fn sentry_each_card() {
+        let supply = supply();
+        let mut game = setup();
+        let alice = game.players[0].id;
+        for card in ["Copper", "Estate", "Silver", "Gold"] {
+            game.put_card(alice, Zone::Deck, &supply[card]);
+        }
+        game.push_effect(alice, sentry().rules[0].1.clone(), None);
+        // 見た2枚を1枚ずつ処理する
+        let EffectStepResult::AskChoice(..) = game.run() else {
+            panic!("選択肢を聞かれるはず");
+        };
+        let estate = game.stack.last().unwrap().focus[0];
+        assert!(game.answer(Answer::Choice(vec![0])).is_ok());
+        let EffectStepResult::AskChoice(..) = game.run() else {
+            panic!("選択肢を聞かれるはず");
+        };
+        let silver = game.stack.last().unwrap().focus[0];
+        assert!(game.answer(Answer::Choice(vec![2])).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+
+        assert_eq!(game.trash, vec![estate]);
+        assert_eq!(game.players[0].deck.last(), Some(&silver));
+        assert_eq!(game.players[0].deck.len(), 2);
+        assert_eq!(game.players[0].hand.len(), 1);
+        assert!(game.players[0].pending.is_empty());
+    }
+
+    #[test]
+    fn repeat_and_restore_focus() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let effect = FocusAll(
+            hand(),
+            Box::new(Sequence(vec![
+                ForEach(focused(), Box::new(PlusCoin(Constant(1)))),
+                Repeat(Constant(3), Box::new(PlusAction(Constant(1)))),
+                MoveCard(focused(), Zone::Discard),
+            ])),
+        );
+        game.push_effect(alice, effect, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.coins, 5);
+        assert_eq!(game.actions, 4);
+        assert!(game.players[0].hand.is_empty());
+        assert_eq!(game.players[0].discard.len(), 10);
+    }
+}
+
+mod binding {
+    use crate::{
+        core::{effect::CardEffect::*, number::Number, number::Number::*, zone::Zone},
+        expansions::{
+            base::remodel,
+            card_util::{focused, hand},
+        },
+        game::effect_stack::{Answer, EffectStepResult},
+        tests::{setup2, supply},
+    };
+
+    #[test]
+    fn remodel_reads_trashed_card() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        for card in ["Silver", "Market", "Gold"] {
+            game.add_supply_pile(&supply[card], 10);
+        }
+        game.push_effect(alice, remodel().rules[0].1.clone(), None);
+        let EffectStepResult::AskTrash(_, _, cards) = game.run() else {
+            panic!("廃棄するカードを聞かれるはず");
+        };
+        let silver = cards.iter().find(|c| c.name == "Silver").unwrap();
+        assert!(game.answer(Answer::Cards(vec![silver.instance_id])).is_ok());
+        // 獲得先を選ぶ時点ではFocusが変わっていても、廃棄したカードのコストで決まる
+        let EffectStepResult::AskGain(_, cards) = game.run() else {
+            panic!("獲得するカードを聞かれるはず");
+        };
+        let names: Vec<&str> = cards.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Silver", "Market"]);
+    }
+
+    #[test]
+    fn scoped_to_resolution() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let effect = Sequence(vec![
+            BindNumber("handSize".to_owned(), CountCard(hand())),
+            FocusAll(
+                hand(),
+                Box::new(Sequence(vec![
+                    BindCards("discarded".to_owned(), focused()),
+                    MoveCard(focused(), Zone::Discard),
+                ])),
+            ),
+            PlusCoin(Number::Bound("handSize".to_owned())),
+            PlusBuy(Number::Bound("discarded".to_owned())),
+        ]);
+        game.push_effect(alice, effect, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.coins, 5);
+        assert_eq!(game.buys, 6);
+
+        // 別の解決からは見えない
+        game.push_effect(alice, PlusCoin(Number::Bound("handSize".to_owned())), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.coins, 5);
+    }
+}
+
+mod number {
+    use crate::{
+        core::{
+            effect::CardEffect::TrashCard,
+            number::Number::*,
+            selector::{CardNameSelector, CardSelector, PlayerSelector},
+            zone::Zone,
+        },
+        expansions::card_util::{all_my_cards, hand},
+        tests::{setup2, supply},
+    };
+
+    #[test]
+    fn counts() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        game.add_supply_pile(&supply["Duchy"], 8);
+        game.put_card(alice, Zone::Hand, &supply["Copper"]);
+        game.put_card(alice, Zone::Play, &supply["Silver"]);
+        game.put_card(bob, Zone::Hand, &supply["Estate"]);
+        game.put_card(bob, Zone::Hand, &supply["Estate"]);
+
+        assert_eq!(game.resolve_number(alice, &CountCard(hand())), 6);
+        assert_eq!(game.resolve_number(alice, &CountDistinctNames(hand())), 5);
+        assert_eq!(game.resolve_number(alice, &TreasuresInPlay), 1);
+        assert_eq!(game.resolve_number(bob, &TreasuresInPlay), 0);
+        assert_eq!(
+            game.resolve_number(alice, &CountPile("Duchy".to_owned())),
+            8
+        );
+        assert_eq!(
+            game.resolve_number(alice, &CountPile("Province".to_owned())),
+            0
+        );
+        assert_eq!(game.resolve_number(alice, &PlayerCount), 2);
+        assert_eq!(
+            game.resolve_number(
+                alice,
+                &OfPlayer(PlayerSelector::Opponents, Box::new(CountCard(hand())))
+            ),
+            2
+        );
+        assert_eq!(
+            game.resolve_number(
+                alice,
+                &Min(Box::new(CountCard(all_my_cards())), Box::new(Constant(10)))
+            ),
+            10
+        );
+        assert_eq!(
+            game.resolve_number(alice, &Max(Box::new(PlayerCount), Box::new(Constant(1)))),
+            2
+        );
+        game.push_effect(
+            alice,
+            TrashCard(CardSelector {
+                name: CardNameSelector::Name("Copper".to_owned()),
+                zone: vec![Zone::Hand],
+            }),
+            None,
+        );
+        game.run();
+        assert_eq!(
+            game.resolve_number(alice, &CountTrash(CardNameSelector::Any)),
+            2
+        );
+    }
+}
+
+mod cond {
+    use crate::{
+        core::{
+            ask_tag::{AskChoiceTag, ChoiceLabel},
+            effect::{CardEffect::*, ChoiceOption, EffectCond::*},
+            number::{Number::*, NumberRange::*},
+            selector::{CardNameSelector, CardSelector, PlayerSelector},
+            turn_phase::TurnPhase,
+            zone::Zone,
+        },
+        game::effect_stack::{Answer, EffectStepResult},
+        tests::{setup2, supply},
+    };
+
+    #[test]
+    fn turn_and_zone_state() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        game.add_supply_pile(&supply["Gold"], 0);
+        assert!(game.resolve_cond(alice, &MyTurn));
+        assert!(!game.resolve_cond(bob, &MyTurn));
+        assert!(game.resolve_cond(alice, &PhaseIs(TurnPhase::Action)));
+        game.phase = TurnPhase::Buy;
+        assert!(game.resolve_cond(alice, &PhaseIs(TurnPhase::Buy)));
+        assert!(game.resolve_cond(alice, &PileEmpty("Gold".to_owned())));
+        assert!(!game.resolve_cond(alice, &PileEmpty("Silver".to_owned())));
+
+        // 場の地下貯蔵庫が元になった効果
+        let cellar = game.players[0].play[0];
+        game.push_effect(
+            alice,
+            If(ThisInPlay, Box::new(PlusCoin(Constant(1)))),
+            Some(cellar),
+        );
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.coins, 1);
+    }
+
+    #[test]
+    fn merchant_once_per_turn() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let merchant_id = game.players[0].pending[0];
+        game.move_card(merchant_id, alice, &Zone::Hand);
+        game.put_card(alice, Zone::Hand, &supply["Silver"]);
+        let silver = |zone| CardSelector {
+            name: CardNameSelector::Name("Silver".to_owned()),
+            zone: vec![zone],
+        };
+        let merchant = || CardSelector {
+            name: CardNameSelector::Name("Merchant".to_owned()),
+            zone: vec![Zone::Hand],
+        };
+        // 商人を使ってから銀貨2枚で+4金、最初の銀貨にだけ+1金
+        game.push_effect(alice, UseCard(silver(Zone::Hand)), None);
+        game.push_effect(alice, UseCard(merchant()), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.coins, 5);
+
+        // 銀貨を使った後で場に出た商人は、このターンもう+1金しない
+        game.start_turn(alice);
+        game.move_card(merchant_id, alice, &Zone::Hand);
+        game.push_effect(alice, UseCard(silver(Zone::Play)), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        game.push_effect(alice, UseCard(silver(Zone::Play)), None);
+        game.push_effect(alice, UseCard(merchant()), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.coins, 8);
+        assert_eq!(game.turn, 1);
+    }
+
+    #[test]
+    fn merchant_with_throne_room() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let merchant_id = game.players[0].pending[0];
+        let throne_room_id = game.players[0].pending[1];
+        game.move_card(merchant_id, alice, &Zone::Hand);
+        game.move_card(throne_room_id, alice, &Zone::Hand);
+        let hand = |name: &str| CardSelector {
+            name: CardNameSelector::Name(name.to_owned()),
+            zone: vec![Zone::Hand],
+        };
+        game.push_effect(alice, UseCard(hand("Silver")), None);
+        game.push_effect(alice, UseCard(hand("Throne Room")), None);
+        let EffectStepResult::AskCard(_, _, _, cards) = game.run() else {
+            panic!("カードを聞かれるはず");
+        };
+        assert!(cards.iter().any(|c| c.instance_id == merchant_id));
+        assert!(game.answer(Answer::Cards(vec![merchant_id])).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        // 商人を2回使ったので、最初の銀貨で+1金が2回
+        assert_eq!(game.coins, 4);
+    }
+
+    #[test]
+    fn opponent_chose() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let option = |tag: &str| ChoiceOption {
+            label: ChoiceLabel::new(tag, tag),
+            cond: None,
+            effect: Noop,
+        };
+        let effect = Sequence(vec![
+            ForEachPlayer(
+                PlayerSelector::Opponents,
+                Box::new(Choose(
+                    AskChoiceTag::new("vote", "選んでください", false),
+                    Exact(Constant(1)),
+                    vec![option("yes"), option("no")],
+                )),
+            ),
+            If(
+                OpponentChose("no".to_owned()),
+                Box::new(PlusCoin(Constant(2))),
+            ),
+        ]);
+        game.push_effect(alice, effect, None);
+        let EffectStepResult::AskChoice(asked, ..) = game.run() else {
+            panic!("選択肢を聞かれるはず");
+        };
+        assert_eq!(asked, game.players[1].id);
+        assert!(game.answer(Answer::Choice(vec![1])).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.coins, 2);
+    }
+}
+
+mod shared_zones {
+    use crate::{
+        core::{
+            ask_tag::AskCardTag,
+            card::CardType::*,
+            effect::{CardEffect::*, GainSource},
+            number::{Number::*, NumberRange::*},
+            selector::{CardNameSelector::*, CardSelector, PlayerSelector},
+            zone::Zone,
+        },
+        expansions::card_util::{focused, in_zone},
+        game::{
+            card_instance::CardAddress,
+            effect_stack::{Answer, EffectStepResult},
+        },
+        tests::{setup2, supply},
+    };
+
+    #[test]
+    fn supply_and_trash() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        for card in ["Copper", "Village", "Smithy"] {
+            game.add_supply_pile(&supply[card], 10);
+        }
+        assert_eq!(
+            game.resolve_number(alice, &CountCard(in_zone(Zone::Supply))),
+            3
+        );
+        assert_eq!(
+            game.resolve_number(
+                alice,
+                &CountCard(in_zone(Zone::SupplyPile("Smithy".to_owned())))
+            ),
+            1
+        );
+        // Lurkerのように、サプライのアクションカードを廃棄し、廃棄置き場から手札に獲得する
+        let effect = Sequence(vec![
+            Select(
+                AskCardTag::new("lurker", "廃棄するアクションカードを選んでください"),
+                Exact(Constant(1)),
+                CardSelector {
+                    name: HasType(Action),
+                    zone: vec![Zone::Supply],
+                },
+                Box::new(TrashCard(focused())),
+            ),
+            GainCard(GainSource::Trash, HasType(Action), Zone::Hand),
+        ]);
+        game.push_effect(alice, effect, None);
+        let EffectStepResult::AskCard(_, _, _, cards) = game.run() else {
+            panic!("カードを聞かれるはず");
+        };
+        assert_eq!(cards.len(), 2);
+        let village = cards[0].instance_id;
+        assert!(game.answer(Answer::Cards(vec![village])).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.supply[1].len(), 9);
+        assert!(game.players[0].hand.contains(&village));
+        assert_eq!(
+            game.resolve_number(alice, &CountCard(in_zone(Zone::Trash))),
+            0
+        );
+    }
+
+    #[test]
+    fn opponent_zone() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        let gold = game.put_card(bob, Zone::Discard, &supply["Gold"]);
+        game.put_card(bob, Zone::Discard, &supply["Estate"]);
+        let opponents_discard = Zone::OfPlayer(PlayerSelector::Opponents, Box::new(Zone::Discard));
+        assert_eq!(
+            game.resolve_number(alice, &CountCard(in_zone(opponents_discard.clone()))),
+            2
+        );
+        let effect = FocusAll(
+            CardSelector {
+                name: HasType(Treasure),
+                zone: vec![opponents_discard],
+            },
+            Box::new(MoveCard(focused(), Zone::Discard)),
+        );
+        game.push_effect(alice, effect, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert!(game.players[0].discard.contains(&gold));
+        assert_eq!(game.players[1].discard.len(), 1);
+        let moved = game.resolve_zone(alice, &Zone::Discard);
+        assert!(moved.iter().any(|c| c.id == gold
+            && matches!(c.address, CardAddress::PlayerOwned(owner, Zone::Discard) if owner == alice)));
+    }
+}
+
+mod cost {
+    use crate::{
+        core::{
+            card::{Card, CardType::*},
+            cost::{CardCost, Cost, CostScope},
+            effect::CardEffect::*,
+            number::{Number::*, NumberRange::*},
+            selector::{CardNameSelector::*, CardSelector},
+            token,
+            zone::Zone,
+        },
+        expansions::card_util::{focused, hand},
+        game::effect_stack::EffectStepResult,
+        tests::{setup2, supply, test_card},
+    };
+
+    #[test]
+    fn this_turn() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.add_supply_pile(&supply["Gold"], 10);
+        assert_eq!(game.resolve_number(alice, &CountCost(hand())), 16);
+        // 橋を2回使う
+        for _ in 0..2 {
+            game.push_effect(
+                alice,
+                ModifyCost(CostScope::ThisTurn, Any, Constant(-1)),
+                None,
+            );
+            assert!(matches!(game.run(), EffectStepResult::End));
+        }
+        // 銅貨0、銀貨1、金貨4、堀0、山賊3
+        assert_eq!(game.resolve_number(alice, &CountCost(hand())), 8);
+        assert!(game.resolve_name(alice, &Cost(Box::new(UpTo(Constant(4)))), &supply["Gold"]));
+
+        game.coins = 3;
+        assert!(game.buy(alice, 0).is_err());
+        game.coins = 4;
+        assert!(game.buy(alice, 0).is_ok());
+        assert_eq!(game.coins, 0);
+        assert_eq!(game.buys, 0);
+
+        game.start_turn(alice);
+        assert_eq!(game.resolve_number(alice, &CountCost(hand())), 16);
+    }
+
+    #[test]
+    fn while_in_play_and_pile() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let cellar = game.players[0].play[0];
+        let silver = game.add_supply_pile(&supply["Silver"], 10);
+        game.push_effect(
+            alice,
+            ModifyCost(CostScope::WhileInPlay, HasType(Action), Constant(-2)),
+            Some(cellar),
+        );
+        game.push_effect(
+            alice,
+            ModifyCost(CostScope::Pile("Silver".to_owned()), Any, Constant(-2)),
+            None,
+        );
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.card_cost(alice, &supply["Bandit"], None), 3);
+        assert_eq!(game.card_cost(alice, &supply["Moat"], None), 0);
+        assert_eq!(game.card_cost(alice, &supply["Silver"], Some(silver)), 1);
+        // 同じ名前でも、その山から来たカードでなければ安くならない
+        assert_eq!(game.card_cost(alice, &supply["Silver"], None), 3);
+        assert_eq!(game.card_cost(alice, &supply["Gold"], None), 6);
+
+        // 場を離れると元に戻る
+        game.push_effect(
+            alice,
+            FocusAll(
+                CardSelector {
+                    name: Name("Cellar".to_owned()),
+                    zone: vec![Zone::Play],
+                },
+                Box::new(MoveCard(focused(), Zone::Discard)),
+            ),
+            None,
+        );
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.card_cost(alice, &supply["Bandit"], None), 5);
+        assert_eq!(game.card_cost(alice, &supply["Silver"], Some(silver)), 1);
+
+        // 後のターンにもう一度使っても、修正は1回分だけ
+        game.start_turn(alice);
+        game.move_card(cellar, alice, &Zone::Play);
+        assert_eq!(game.card_cost(alice, &supply["Bandit"], None), 5);
+        game.push_effect(
+            alice,
+            ModifyCost(CostScope::WhileInPlay, HasType(Action), Constant(-2)),
+            Some(cellar),
+        );
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.card_cost(alice, &supply["Bandit"], None), 3);
+    }
+
+    fn card(name: &str, coin: i32, potion: i32, debt: i32) -> Card {
+        Card {
+            cost: CardCost {
+                coin: Constant(coin),
+                potion,
+                debt,
+            },
+            ..test_card(name, vec![Action], vec![])
+        }
+    }
+
+    #[test]
+    fn potion_and_debt() {
+        let supply = supply();
+        let familiar = card("Familiar", 3, 1, 0);
+        let engineer = card("Engineer", 0, 0, 4);
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        // $3ポーションと$4は比べられない
+        let potion_cost = game.full_cost(alice, &familiar, None, true);
+        assert!(!potion_cost.leq(&Cost::coin(4)));
+        assert!(!Cost::coin(4).leq(&potion_cost));
+        assert!(!game.resolve_name(alice, &Cost(Box::new(UpTo(Constant(4)))), &familiar));
+        assert!(!game.resolve_name(alice, &Cost(Box::new(UpTo(Constant(4)))), &engineer));
+
+        // 手札の使い魔より$2多いまで
+        game.put_card(alice, Zone::Hand, &familiar);
+        let in_hand = CardSelector {
+            name: Name("Familiar".to_owned()),
+            zone: vec![Zone::Hand],
+        };
+        let up_to = CostUpTo(Box::new(in_hand.clone()), Box::new(Constant(2)));
+        assert!(game.resolve_name(alice, &up_to, &supply["Silver"]));
+        assert!(game.resolve_name(alice, &up_to, &familiar));
+        assert!(!game.resolve_name(alice, &up_to, &supply["Gold"]));
+        let less = CostLessThan(Box::new(in_hand));
+        assert!(game.resolve_name(alice, &less, &supply["Silver"]));
+        assert!(!game.resolve_name(alice, &less, &supply["Gold"]));
+
+        let familiar_pile = game.add_supply_pile(&familiar, 10);
+        let engineer_pile = game.add_supply_pile(&engineer, 10);
+        game.buys = 3;
+        game.coins = 3;
+        assert!(game.buy(alice, familiar_pile).is_err());
+        game.push_effect(alice, PlusPotion(Constant(1)), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert!(game.buy(alice, familiar_pile).is_ok());
+        assert_eq!((game.coins, game.potions), (0, 0));
+
+        // 負債を返すまでは買えない
+        assert!(game.buy(alice, engineer_pile).is_ok());
+        assert_eq!(game.players[0].token(token::DEBT), 4);
+        game.coins = 4;
+        assert!(game.buy(alice, engineer_pile).is_err());
+        assert!(game.spend_tokens(alice, token::DEBT, 4).is_ok());
+        assert_eq!(game.coins, 0);
+        assert!(game.buy(alice, engineer_pile).is_ok());
+    }
+}
+
+mod static_ability {
+    use crate::{
+        core::{
+            card::CardType::*,
+            cost::CostScope,
+            effect::{CardEffect::*, EffectCond::*, EffectTrigger::*, GainSource},
+            number::{Number::*, NumberRange::Exact},
+            selector::{CardNameSelector::*, CardSelector},
+            zone::Zone,
+        },
+        expansions::card_util::in_zone,
+        game::effect_stack::EffectStepResult,
+        tests::{setup2, supply, test_card},
+    };
+
+    #[test]
+    fn hoard_while_in_play() {
+        let supply = supply();
+        // 埋蔵金：場にある間、勝利点カードを買うたびに金貨を獲得する
+        let hoard = test_card(
+            "Hoard",
+            vec![Treasure],
+            vec![(
+                WhileIn(Zone::Play, Box::new(CardBought)),
+                If(
+                    FocusedIs(HasType(Victory)),
+                    Box::new(GainCard(
+                        GainSource::Supply,
+                        Name("Gold".to_owned()),
+                        Zone::Discard,
+                    )),
+                ),
+            )],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.add_supply_pile(&supply["Estate"], 8);
+        game.add_supply_pile(&supply["Gold"], 30);
+        game.put_card(alice, Zone::Play, &hoard);
+        game.coins = 4;
+        game.buys = 2;
+        assert!(game.buy(alice, 0).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.supply[1].len(), 29);
+
+        // 場を離れたら反応しない
+        game.push_effect(alice, MoveCard(in_zone(Zone::Play), Zone::Discard), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert!(game.buy(alice, 0).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.supply[1].len(), 29);
+    }
+
+    #[test]
+    fn attack_immunity() {
+        let supply = supply();
+        let lighthouse = test_card(
+            "Lighthouse",
+            vec![Treasure],
+            vec![(WhileIn(Zone::Play, Box::new(Attacked)), PreventDefault)],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        game.add_supply_pile(&supply["Curse"], 10);
+        let witch = AttackAllOpponents(Box::new(GainCard(
+            GainSource::Supply,
+            Name("Curse".to_owned()),
+            Zone::Discard,
+        )));
+
+        game.push_effect(alice, witch.clone(), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[1].discard.len(), 1);
+
+        // 手札の堀、場の灯台はどちらも攻撃を防ぐ
+        let moat = game.put_card(bob, Zone::Hand, &supply["Moat"]);
+        game.push_effect(alice, witch.clone(), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[1].discard.len(), 1);
+
+        game.move_card(moat, bob, &Zone::Discard);
+        game.put_card(bob, Zone::Play, &lighthouse);
+        game.push_effect(alice, witch, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[1].discard.len(), 2);
+    }
+
+    #[test]
+    fn attacked_reactions_resolve() {
+        let supply = supply();
+        // 攻撃されたときに銀貨を獲得するだけで、攻撃は防がない
+        let alarm = test_card(
+            "Alarm",
+            vec![Treasure],
+            vec![(
+                WhileIn(Zone::Hand, Box::new(Attacked)),
+                GainCard(GainSource::Supply, Name("Silver".to_owned()), Zone::Hand),
+            )],
+        );
+        // 自分の手番でなければ防がない
+        let bell = test_card(
+            "Bell",
+            vec![Treasure],
+            vec![(
+                WhileIn(Zone::Play, Box::new(Attacked)),
+                If(MyTurn, Box::new(PreventDefault)),
+            )],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        game.add_supply_pile(&supply["Curse"], 10);
+        game.add_supply_pile(&supply["Silver"], 10);
+        game.put_card(bob, Zone::Hand, &alarm);
+        game.put_card(bob, Zone::Play, &bell);
+        let witch = AttackAllOpponents(Box::new(GainCard(
+            GainSource::Supply,
+            Name("Curse".to_owned()),
+            Zone::Discard,
+        )));
+        game.push_effect(alice, witch, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[1].hand.len(), 2);
+        assert_eq!(game.players[1].discard.len(), 1);
+    }
+
+    #[test]
+    fn required_select_without_candidates() {
+        let supply = supply();
+        let highway = test_card(
+            "Highway",
+            vec![Treasure],
+            vec![(
+                WhileIn(Zone::Play, Box::new(Continuous)),
+                ModifyCost(CostScope::WhileInPlay, Any, Constant(-1)),
+            )],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let trash = TrashSelect(
+            Exact(Constant(1)),
+            CardSelector {
+                name: Cost(Box::new(Exact(Constant(3)))),
+                zone: vec![Zone::Hand],
+            },
+            Box::new(Noop),
+        );
+        game.push_effect(alice, trash.clone(), None);
+        assert!(matches!(game.run(), EffectStepResult::AskTrash(..)));
+
+        // 公道で$3のカードが無くなったら、聞かずに飛ばす
+        let mut game = setup2(&supply);
+        game.put_card(alice, Zone::Play, &highway);
+        game.push_effect(alice, trash, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[0].hand.len(), 5);
+    }
+
+    #[test]
+    fn continuous_cost() {
+        let supply = supply();
+        let highway = test_card(
+            "Highway",
+            vec![Treasure],
+            vec![(
+                WhileIn(Zone::Play, Box::new(Continuous)),
+                ModifyCost(CostScope::WhileInPlay, Any, Constant(-1)),
+            )],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        let id = game.put_card(bob, Zone::Play, &highway);
+        assert_eq!(game.card_cost(alice, &supply["Gold"], None), 5);
+        game.move_card(id, bob, &Zone::Hand);
+        assert_eq!(game.card_cost(alice, &supply["Gold"], None), 6);
+    }
+}
+
+mod triggers {
+    use crate::{
+        core::{
+            card::CardType::*,
+            effect::{
+                CardEffect::{self, *},
+                EffectTrigger::*,
+                GainSource,
+            },
+            number::Number::*,
+            selector::{CardNameSelector::*, CardSelector},
+            zone::Zone,
+        },
+        expansions::card_util::in_zone,
+        game::effect_stack::EffectStepResult,
+        tests::{setup, setup2, supply, test_card},
+    };
+
+    fn gain_silver() -> CardEffect {
+        GainCard(GainSource::Supply, Name("Silver".to_owned()), Zone::Discard)
+    }
+
+    #[test]
+    fn trashed_at_once() {
+        let supply = supply();
+        // 封土：廃棄されたとき、銀貨を3枚獲得する
+        let feodum = test_card(
+            "Feodum",
+            vec![Action],
+            vec![(
+                ThisCard(Box::new(CardTrashed)),
+                Repeat(Constant(3), Box::new(gain_silver())),
+            )],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.add_supply_pile(&supply["Silver"], 40);
+        game.put_card(alice, Zone::Hand, &feodum);
+        game.put_card(alice, Zone::Hand, &feodum);
+        let effect = TrashCard(CardSelector {
+            name: Name("Feodum".to_owned()),
+            zone: vec![Zone::Hand],
+        });
+        game.push_effect(alice, effect, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.trash.len(), 2);
+        assert_eq!(game.supply[0].len(), 34);
+    }
+
+    #[test]
+    fn discarded_not_moved() {
+        let supply = supply();
+        // トンネル：捨て札にしたとき、金貨を獲得する
+        let tunnel = test_card(
+            "Tunnel",
+            vec![Action],
+            vec![(
+                ThisCard(Box::new(CardDiscarded)),
+                GainCard(GainSource::Supply, Name("Gold".to_owned()), Zone::Discard),
+            )],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.add_supply_pile(&supply["Gold"], 30);
+        game.put_card(alice, Zone::Hand, &tunnel);
+        game.put_card(alice, Zone::Aside, &tunnel);
+        let selector = |zone| CardSelector {
+            name: Name("Tunnel".to_owned()),
+            zone: vec![zone],
+        };
+        game.push_effect(alice, MoveCard(selector(Zone::Aside), Zone::Discard), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.supply[0].len(), 30);
+        game.push_effect(alice, DiscardCard(selector(Zone::Hand)), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.supply[0].len(), 29);
+    }
+
+    #[test]
+    fn gained_and_bought_while_in_play() {
+        let supply = supply();
+        // 場にある間、自分がカードを獲得するたびに+1金、購入するたびに+1購入
+        let watcher = test_card(
+            "Watcher",
+            vec![Action],
+            vec![
+                (
+                    WhileIn(Zone::Play, Box::new(CardGained)),
+                    PlusCoin(Constant(1)),
+                ),
+                (
+                    WhileIn(Zone::Play, Box::new(CardBought)),
+                    PlusBuy(Constant(1)),
+                ),
+            ],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        game.add_supply_pile(&supply["Silver"], 40);
+        game.put_card(alice, Zone::Play, &watcher);
+        game.push_effect(alice, gain_silver(), None);
+        game.push_effect(bob, gain_silver(), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.coins, 1);
+
+        game.coins = 3;
+        assert!(game.buy(alice, 0).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.coins, 1);
+        assert_eq!(game.buys, 1);
+    }
+
+    #[test]
+    fn shuffled() {
+        let supply = supply();
+        let reshuffler = test_card(
+            "Reshuffler",
+            vec![Action],
+            vec![(
+                WhileIn(Zone::Play, Box::new(DeckShuffled)),
+                PlusCoin(Constant(1)),
+            )],
+        );
+        // 隠し財産のように、シャッフルで混ぜられたカード自身の能力
+        let stash = test_card(
+            "Stash",
+            vec![Action],
+            vec![(ThisCard(Box::new(DeckShuffled)), PlusBuy(Constant(1)))],
+        );
+        let mut game = setup();
+        let alice = game.players[0].id;
+        game.put_card(alice, Zone::Play, &reshuffler);
+        for _ in 0..3 {
+            game.put_card(alice, Zone::Discard, &supply["Copper"]);
+        }
+        game.put_card(alice, Zone::Discard, &stash);
+        game.push_effect(alice, PlusDraw(Constant(2)), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.coins, 1);
+        assert_eq!(game.buys, 2);
+        assert_eq!(game.resolve_zone(alice, &Zone::Hand).len(), 2);
+        assert_eq!(
+            game.resolve_number(alice, &CountCard(in_zone(Zone::Deck))),
+            2
+        );
+    }
+
+    #[test]
+    fn discarded_from_play_at_cleanup() {
+        let supply = supply();
+        // 宝物庫のように、場から捨て札にしたときの能力
+        let treasury = test_card(
+            "Treasury",
+            vec![Action],
+            vec![(ThisCard(Box::new(CardDiscarded)), PlusBuy(Constant(1)))],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.put_card(alice, Zone::Play, &treasury);
+        game.cleanup(alice);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.buys, 2);
+        assert!(game.players[0].play.is_empty());
+        assert_eq!(game.players[0].hand.len(), 5);
+        assert!(game.players[0].deck.is_empty());
+        assert_eq!(game.players[0].discard.len(), 14);
+    }
+
+    #[test]
+    fn stays_in_play_at_cleanup() {
+        let supply = supply();
+        // 持続カードのように、クリーンアップで場に残る
+        let duration = test_card(
+            "Duration",
+            vec![Action],
+            vec![(ThisCard(Box::new(Cleanup)), PreventDefault)],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let id = game.put_card(alice, Zone::Play, &duration);
+        game.cleanup(alice);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[0].play, vec![id]);
+        assert_eq!(game.players[0].discard.len(), 13);
+
+        // 場以外から捨て札にするときは働かない
+        game.move_card(id, alice, &Zone::Hand);
+        game.push_effect(alice, DiscardCard(in_zone(Zone::Hand)), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert!(game.players[0].discard.contains(&id));
+    }
+}
+
+mod replacement {
+    use crate::{
+        core::{
+            ask_tag::AskOptionTag,
+            card::CardType::*,
+            effect::{
+                ActionKind,
+                CardEffect::{self, *},
+                EffectTrigger::{self, *},
+                GainSource,
+            },
+            number::Number::*,
+            selector::CardNameSelector::*,
+            zone::Zone,
+        },
+        game::effect_stack::{Answer, EffectStepResult},
+        tests::{setup2, supply, test_card},
+    };
+
+    fn while_in(zone: Zone, kind: ActionKind) -> EffectTrigger {
+        WhileIn(zone, Box::new(Replace(kind)))
+    }
+
+    fn gain(name: &str) -> CardEffect {
+        GainCard(GainSource::Supply, Name(name.to_owned()), Zone::Discard)
+    }
+
+    #[test]
+    fn redirect_gain() {
+        let supply = supply();
+        // 見張り塔：獲得するとき、そのカードを山札の上に置いてもよい
+        let watchtower = test_card(
+            "Watchtower",
+            vec![Action, Reaction],
+            vec![(
+                while_in(Zone::Hand, ActionKind::Gain),
+                Optional(
+                    AskOptionTag::new("watchtower", "山札の上に置きますか？", None),
+                    Box::new(Redirect(Zone::DeckTop)),
+                ),
+            )],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.add_supply_pile(&supply["Silver"], 40);
+        game.put_card(alice, Zone::Hand, &watchtower);
+        game.push_effect(alice, gain("Silver"), None);
+        assert!(matches!(game.run(), EffectStepResult::AskOptional(..)));
+        assert!(game.answer(Answer::YesNo(true)).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.supply[0].len(), 39);
+        let top = game.resolve_zone(alice, &Zone::DeckTop);
+        assert_eq!(top[0].card.name, "Silver");
+    }
+
+    #[test]
+    fn substitute_gain() {
+        let supply = supply();
+        // 交易商：獲得するとき、代わりに銀貨を獲得してもよい
+        let trader = test_card(
+            "Trader",
+            vec![Action, Reaction],
+            vec![(
+                while_in(Zone::Hand, ActionKind::Gain),
+                Optional(
+                    AskOptionTag::new("trader", "代わりに銀貨を獲得しますか？", None),
+                    Box::new(Sequence(vec![PreventDefault, gain("Silver")])),
+                ),
+            )],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.add_supply_pile(&supply["Gold"], 30);
+        game.add_supply_pile(&supply["Silver"], 40);
+        game.put_card(alice, Zone::Hand, &trader);
+        game.push_effect(alice, gain("Gold"), None);
+        assert!(matches!(game.run(), EffectStepResult::AskOptional(..)));
+        assert!(game.answer(Answer::YesNo(true)).is_ok());
+        // 代わりに獲得する銀貨にも交易商は使えるが、使わない
+        assert!(matches!(game.run(), EffectStepResult::AskOptional(..)));
+        assert!(game.answer(Answer::YesNo(false)).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.supply[0].len(), 30);
+        assert_eq!(game.supply[1].len(), 39);
+        let discard = game.resolve_zone(alice, &Zone::Discard);
+        assert_eq!(discard.last().unwrap().card.name, "Silver");
+    }
+
+    #[test]
+    fn player_chooses_order() {
+        let supply = supply();
+        let to_hand = test_card(
+            "ToHand",
+            vec![Action, Reaction],
+            vec![(while_in(Zone::Hand, ActionKind::Gain), Redirect(Zone::Hand))],
+        );
+        let to_deck = test_card(
+            "ToDeck",
+            vec![Action, Reaction],
+            vec![(
+                while_in(Zone::Play, ActionKind::Gain),
+                Redirect(Zone::DeckTop),
+            )],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.add_supply_pile(&supply["Silver"], 40);
+        let first = game.put_card(alice, Zone::Hand, &to_hand);
+        let second = game.put_card(alice, Zone::Play, &to_deck);
+        game.push_effect(alice, gain("Silver"), None);
+        let EffectStepResult::AskReplacement(_, cards) = game.run() else {
+            panic!("置換効果の順番を聞かれるはず");
+        };
+        assert_eq!(cards.len(), 2);
+        assert!(game.answer(Answer::Cards(vec![first, second])).is_err());
+        assert!(game.answer(Answer::Cards(vec![first])).is_ok());
+        // 残りが1つなら聞かれない。最後に適用した行き先になる
+        assert!(matches!(game.run(), EffectStepResult::End));
+        let top = game.resolve_zone(alice, &Zone::DeckTop);
+        assert_eq!(top[0].card.name, "Silver");
+    }
+
+    #[test]
+    fn draw_waits_for_replacement() {
+        let supply = supply();
+        // 引くとき、代わりに捨て札にする
+        let cursed = test_card(
+            "Cursed",
+            vec![Action, Reaction],
+            vec![(
+                ThisCard(Box::new(Replace(ActionKind::Draw))),
+                Redirect(Zone::Discard),
+            )],
+        );
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let id = game.put_card(alice, Zone::Deck, &cursed);
+        game.push_effect(alice, PlusDraw(Constant(2)), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert!(game.players[0].discard.contains(&id));
+        // 置換されたカードの後で、残りの1枚を引く
+        assert_eq!(game.players[0].hand.len(), 6);
+        assert_eq!(game.players[0].deck.len(), 4);
+    }
+}
+
+mod history {
+    use crate::{
+        core::{
+            card::CardType::*,
+            effect::{CardEffect::*, EffectCond::*},
+            history::{HistoryKind, TurnScope},
+            number::Number::*,
+            selector::{CardNameSelector::*, CardSelector, PlayerSelector},
+            zone::Zone,
+        },
+        game::effect_stack::EffectStepResult,
+        tests::{setup2, supply},
+    };
+
+    #[test]
+    fn actions_played() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        for _ in 0..3 {
+            game.put_card(alice, Zone::Hand, &supply["Village"]);
+        }
+        let villages = CardSelector {
+            name: Name("Village".to_owned()),
+            zone: vec![Zone::Hand],
+        };
+        game.push_effect(alice, UseCard(villages), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        // 共謀者：このターンにアクションを3回以上使っていれば
+        let conspirator = Geq(
+            CountHistory(TurnScope::ThisTurn, HistoryKind::Played, HasType(Action)),
+            Constant(3),
+        );
+        assert!(game.resolve_cond(alice, &conspirator));
+        assert_eq!(game.actions, 7);
+
+        game.start_turn(bob);
+        assert!(!game.resolve_cond(alice, &conspirator));
+        let previous = CountHistory(TurnScope::PreviousTurn, HistoryKind::Played, Any);
+        assert_eq!(game.resolve_number(alice, &previous), 3);
+    }
+
+    #[test]
+    fn bought_and_gained() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        let estate = game.add_supply_pile(&supply["Estate"], 8);
+        game.coins = 2;
+        assert!(game.buy(alice, estate).is_ok());
+        // 宝物庫：このターンに勝利点カードを買っていなければ
+        let treasury = Happened(TurnScope::ThisTurn, HistoryKind::Bought, HasType(Victory));
+        assert!(game.resolve_cond(alice, &treasury));
+        assert!(!game.resolve_cond(bob, &treasury));
+
+        // 密輸人：右隣のプレイヤーが直前のターンに獲得したカード
+        game.start_turn(bob);
+        let smugglers = OfPlayer(
+            PlayerSelector::RightOfTarget,
+            Box::new(CountHistory(
+                TurnScope::PreviousTurn,
+                HistoryKind::Gained,
+                Any,
+            )),
+        );
+        assert_eq!(game.resolve_number(bob, &smugglers), 1);
+    }
+}
+
+mod extra_turn {
+    use crate::{
+        core::{
+            ask_tag::AskCardTag,
+            effect::{CardEffect::*, EffectCond::*, GainSource},
+            number::{Number::*, NumberRange::*},
+            selector::{CardNameSelector::*, CardSelector, PlayerSelector},
+            turn_rule::TurnRule,
+            zone::Zone,
+        },
+        expansions::card_util::hand,
+        game::effect_stack::{Answer, EffectStepResult},
+        tests::{setup2, supply},
+    };
+
+    #[test]
+    fn outpost() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        let outpost = ExtraTurn(PlayerSelector::Me, vec![TurnRule::HandSize(3)]);
+        game.push_effect(alice, outpost.clone(), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.next_hand_size(), 3);
+        game.cleanup(alice);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[0].hand.len(), 3);
+        assert_eq!(game.next_turn(), alice);
+        assert!(game.resolve_cond(alice, &PreviousTurnMine));
+
+        // 追加ターン中に使った前哨地では、3ターン続けられない
+        game.push_effect(alice, outpost, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.next_hand_size(), 5);
+        game.cleanup(alice);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[0].hand.len(), 5);
+        assert_eq!(game.next_turn(), bob);
+        assert_eq!(game.next_turn(), alice);
+    }
+
+    #[test]
+    fn turn_rules() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let silver = game.add_supply_pile(&supply["Silver"], 40);
+        let rules = vec![TurnRule::NoBuy, TurnRule::PlayFromHand(1)];
+        game.push_effect(alice, ExtraTurn(PlayerSelector::Me, rules), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        game.next_turn();
+        game.coins = 3;
+        assert!(game.buy(alice, silver).is_err());
+        let copper = |name: &str| CardSelector {
+            name: Name(name.to_owned()),
+            zone: vec![Zone::Hand],
+        };
+        game.push_effect(alice, UseCard(copper("Copper")), None);
+        game.push_effect(alice, UseCard(copper("Silver")), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        // 手札から使えるのは1枚だけ
+        assert_eq!(game.coins, 5);
+        assert_eq!(game.players[0].play.len(), 4);
+    }
+
+    #[test]
+    fn possession() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        game.add_supply_pile(&supply["Silver"], 40);
+        let estate = game.put_card(bob, Zone::Hand, &supply["Estate"]);
+        let possession = ExtraTurn(PlayerSelector::LeftOfTarget, vec![TurnRule::Possessed]);
+        game.push_effect(alice, possession, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.next_turn(), bob);
+        assert_eq!(game.controller, Some(alice));
+
+        // ボブの決定はアリスが行う
+        let effect = Select(
+            AskCardTag::new("possession", "カードを選んでください"),
+            Exact(Constant(1)),
+            hand(),
+            Box::new(Noop),
+        );
+        game.push_effect(bob, effect, None);
+        let EffectStepResult::AskCard(asked, ..) = game.run() else {
+            panic!("カードを聞かれるはず");
+        };
+        assert_eq!(asked, alice);
+        assert!(game.answer(Answer::Cards(vec![estate])).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+
+        // 獲得したカードはアリスのもとへ、廃棄したカードは脇に置かれる
+        let gain = GainCard(GainSource::Supply, Name("Silver".to_owned()), Zone::Discard);
+        let trash = TrashCard(CardSelector {
+            name: Name("Estate".to_owned()),
+            zone: vec![Zone::Hand],
+        });
+        game.push_effect(bob, Sequence(vec![gain, trash]), None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[0].discard.len(), 6);
+        assert!(game.players[1].discard.is_empty());
+        assert!(game.trash.is_empty());
+        assert_eq!(game.players[1].aside, vec![estate]);
+
+        // ターンが終わればボブの捨て札に戻り、通常の手番順に戻る
+        assert_eq!(game.next_turn(), bob);
+        assert_eq!(game.controller, None);
+        assert_eq!(game.players[1].discard, vec![estate]);
+    }
+}
+
+mod tokens {
+    use crate::{
+        core::{
+            effect::CardEffect::*, number::Number::*, selector::PlayerSelector, token, zone::Zone,
+        },
+        expansions::card_util::in_zone,
+        game::effect_stack::EffectStepResult,
+        tests::{setup2, supply},
+    };
+
+    #[test]
+    fn vp_and_coffers() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let vp = game.calculate_vp(alice);
+        let effect = Sequence(vec![
+            AddTokens(token::VP.to_owned(), Constant(2)),
+            AddTokens(token::COFFERS.to_owned(), Constant(3)),
+            AddTokens(token::COFFERS.to_owned(), Constant(-1)),
+        ]);
+        game.push_effect(alice, effect, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.calculate_vp(alice), vp + 2);
+        let coffers = CountTokens(token::COFFERS.to_owned());
+        assert_eq!(game.resolve_number(alice, &coffers), 2);
+
+        assert!(game.spend_tokens(alice, token::COFFERS, 3).is_err());
+        assert!(game.spend_tokens(alice, token::COFFERS, 2).is_ok());
+        assert_eq!(game.coins, 2);
+        assert_eq!(game.resolve_number(alice, &coffers), 0);
+    }
+
+    #[test]
+    fn mats() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let bob = game.players[1].id;
+        game.secret_mats.push("native_village".to_owned());
+        let native_village = Zone::Mat("native_village".to_owned());
+        let island = Zone::Mat("island".to_owned());
+        let effect = Sequence(vec![
+            MoveCard(in_zone(Zone::DeckTop), native_village.clone()),
+            MoveCard(in_zone(Zone::DeckTop), island.clone()),
+        ]);
+        let all = game.resolve_zone(alice, &Zone::AllMyCards).len();
+        game.push_effect(alice, effect, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.resolve_zone(alice, &native_village).len(), 1);
+        assert_eq!(game.resolve_zone(alice, &Zone::AllMyCards).len(), all);
+        assert!(!game.can_see_mat(bob, alice, "native_village"));
+        assert!(game.can_see_mat(bob, alice, "island"));
+
+        // 他のプレイヤーからは、秘密のマットのカードを選べない
+        let of_alice =
+            |zone: &Zone| Zone::OfPlayer(PlayerSelector::Opponents, Box::new(zone.clone()));
+        game.push_effect(bob, Noop, None);
+        assert!(game
+            .resolve_zone(bob, &of_alice(&native_village))
+            .is_empty());
+        assert_eq!(game.resolve_zone(bob, &of_alice(&island)).len(), 1);
+    }
+}
+
+mod supply_model {
+    use crate::{
+        core::{
+            effect::{CardEffect::*, GainSource},
+            number::Number::*,
+            selector::CardNameSelector::*,
+            zone::Zone,
+        },
+        expansions::card_util::in_zone,
+        game::effect_stack::EffectStepResult,
+        tests::{setup2, supply},
+    };
+
+    #[test]
+    fn split_pile() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        // 野営地／略奪品のように、上の2枚と下の2枚で違うカード
+        let pile = game.add_mixed_pile(
+            "Encampment",
+            &[(&supply["Estate"], 2), (&supply["Duchy"], 2)],
+            false,
+        );
+        let gain = GainCard(GainSource::Supply, Any, Zone::Discard);
+        let top = |game: &crate::game::game::Game| {
+            game.resolve_zone(alice, &Zone::SupplyPile("Encampment".to_owned()))[0]
+                .card
+                .name
+                .clone()
+        };
+        assert_eq!(top(&game), "Estate");
+        for _ in 0..2 {
+            game.push_effect(alice, gain.clone(), None);
+            assert!(matches!(game.run(), EffectStepResult::End));
+        }
+        assert_eq!(top(&game), "Duchy");
+        assert_eq!(game.supply[pile].len(), 2);
+        assert_eq!(
+            game.resolve_number(alice, &CountPile("Encampment".to_owned())),
+            2
+        );
+    }
+
+    #[test]
+    fn non_supply_pile() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.add_supply_pile(&supply["Silver"], 0);
+        let spoils = game.add_non_supply_pile(&supply["Gold"], 1);
+        assert_eq!(game.resolve_number(alice, &CountEmptyPiles), 1);
+        assert!(game
+            .resolve_selector(alice, &in_zone(Zone::Supply))
+            .is_empty());
+        game.coins = 6;
+        assert!(game.buy(alice, spoils).is_err());
+
+        let gain = GainCard(GainSource::NonSupply, Name("Gold".to_owned()), Zone::Hand);
+        game.push_effect(alice, gain, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        assert_eq!(game.players[0].hand.len(), 6);
+        // 空になったサプライ外の山は、空の山に数えない
+        assert_eq!(game.resolve_number(alice, &CountEmptyPiles), 1);
+    }
+}
+
+mod pile_tokens {
+    use crate::{
+        core::{
+            ask_tag::AskCardTag,
+            effect::{CardEffect::*, EffectCond, EffectTrigger, GainSource},
+            number::Number::*,
+            selector::{CardNameSelector::*, PileSelector},
+            zone::Zone,
+        },
+        game::effect_stack::{Answer, EffectStepResult},
+        tests::{setup2, supply},
+    };
+
+    #[test]
+    fn embargo() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        let silver = game.add_supply_pile(&supply["Silver"], 10);
+        game.add_supply_pile(&supply["Curse"], 10);
+        game.add_supply_pile(&supply["Gold"], 0);
+        game.pile_token_rules.push((
+            "embargo".to_owned(),
+            EffectTrigger::CardBought,
+            Repeat(
+                CountPileTokens(PileSelector::OfFocused, "embargo".to_owned()),
+                Box::new(GainCard(
+                    GainSource::Supply,
+                    Name("Curse".to_owned()),
+                    Zone::Discard,
+                )),
+            ),
+        ));
+
+        let embargo = ChoosePile(
+            AskCardTag::new("embargo", "禁輸トークンを置く山を選んでください"),
+            Any,
+            Box::new(AddPileTokens(
+                PileSelector::Chosen,
+                "embargo".to_owned(),
+                Constant(1),
+            )),
+        );
+        for _ in 0..2 {
+            game.push_effect(alice, embargo.clone(), None);
+            // 空の山も選べる
+            let EffectStepResult::AskPile(_, _, candidates) = game.run() else {
+                panic!("山を選ぶはず");
+            };
+            assert_eq!(candidates.len(), 3);
+            assert!(game.answer(Answer::Pile(99)).is_err());
+            assert!(game.answer(Answer::Pile(silver)).is_ok());
+            assert!(matches!(game.run(), EffectStepResult::End));
+        }
+        let tokens = CountPileTokens(
+            PileSelector::Named("Silver".to_owned()),
+            "embargo".to_owned(),
+        );
+        assert_eq!(game.resolve_number(alice, &tokens), 2);
+
+        game.coins = 3;
+        assert!(game.buy(alice, silver).is_ok());
+        assert!(matches!(game.run(), EffectStepResult::End));
+        let curses = game
+            .resolve_zone(alice, &Zone::Discard)
+            .iter()
+            .filter(|c| c.card.name == "Curse")
+            .count();
+        assert_eq!(curses, 2);
+    }
+
+    #[test]
+    fn count_and_cond() {
+        let supply = supply();
+        let mut game = setup2(&supply);
+        let alice = game.players[0].id;
+        game.add_supply_pile(&supply["Estate"], 8);
+        game.add_supply_pile(&supply["Duchy"], 8);
+        game.add_non_supply_pile(&supply["Gold"], 1);
+        // サプライの山すべてにトークンを置いてから、一部を取り除く
+        let effect = Sequence(vec![
+            AddPileTokens(PileSelector::All, "trade_route".to_owned(), Constant(1)),
+            AddPileTokens(
+                PileSelector::Named("Duchy".to_owned()),
+                "trade_route".to_owned(),
+                Constant(-3),
+            ),
+        ]);
+        game.push_effect(alice, effect, None);
+        assert!(matches!(game.run(), EffectStepResult::End));
+        let all = CountPileTokens(PileSelector::All, "trade_route".to_owned());
+        assert_eq!(game.resolve_number(alice, &all), 1);
+        let has = |name: &str| {
+            EffectCond::PileHasToken(
+                PileSelector::Named(name.to_owned()),
+                "trade_route".to_owned(),
+            )
+        };
+        assert!(game.resolve_cond(alice, &has("Estate")));
+        assert!(!game.resolve_cond(alice, &has("Duchy")));
+        // サプライ外の山には置かない
+        assert!(!game.resolve_cond(alice, &has("Gold")));
+    }
+}